@@ -0,0 +1,406 @@
+use serde::Serialize;
+
+use crate::chunk_ref::ChunkRefs;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+use crate::Result;
+
+/// Entropy in bits per byte above which data looks compressed or encrypted rather than like text
+/// or structured metadata. Applies to data of a few KiB and more; see [`scaled_threshold`] for
+/// shorter data.
+pub const HIGH_ENTROPY: f64 = 7.5;
+
+/// Scales an entropy threshold for long data down to `len` bytes.
+///
+/// `len` bytes can't have an entropy above `log2(len)`, so a fixed threshold would miss any short
+/// payload. Instead the threshold is taken relative to the entropy random data of that length
+/// reaches, the log of the number of distinct byte values it is expected to contain.
+pub fn scaled_threshold(threshold: f64, len: usize) -> f64 {
+    let distinct = 256.0 * (1.0 - (255.0f64 / 256.0).powf(len as f64));
+    threshold * distinct.max(1.0).log2() / 8.0
+}
+
+/// Data in a file that could hold a hidden payload. Offsets are byte offsets into the file,
+/// pointing at the start of the chunk (its length field) or of the data.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Finding {
+    /// An ancillary chunk of a type the spec doesn't define.
+    UnknownChunk {
+        offset: usize,
+        chunk_type: String,
+        private: bool,
+        len: usize,
+        entropy: f64,
+    },
+    /// A chunk whose type code isn't four ASCII letters, which decoders can't handle.
+    InvalidChunkType {
+        offset: usize,
+        chunk_type: String,
+        len: usize,
+        entropy: f64,
+    },
+    /// A chunk after `IEND`, where decoders stop reading.
+    AfterIend {
+        offset: usize,
+        chunk_type: String,
+        len: usize,
+        entropy: f64,
+    },
+    /// Bytes after `IEND` that don't form chunks.
+    TrailingData {
+        offset: usize,
+        len: usize,
+        entropy: f64,
+    },
+}
+
+impl Finding {
+    /// Number of bytes that could hold hidden data.
+    pub fn size(&self) -> usize {
+        match self {
+            Finding::UnknownChunk { len, .. }
+            | Finding::InvalidChunkType { len, .. }
+            | Finding::AfterIend { len, .. }
+            | Finding::TrailingData { len, .. } => *len,
+        }
+    }
+
+    pub fn entropy(&self) -> f64 {
+        match self {
+            Finding::UnknownChunk { entropy, .. }
+            | Finding::InvalidChunkType { entropy, .. }
+            | Finding::AfterIend { entropy, .. }
+            | Finding::TrailingData { entropy, .. } => *entropy,
+        }
+    }
+
+    /// Checks if the entropy exceeds `threshold`, scaled to the size of the data.
+    pub fn exceeds(&self, threshold: f64) -> bool {
+        self.entropy() > scaled_threshold(threshold, self.size())
+    }
+
+    /// Checks if the data looks compressed or encrypted, see [`HIGH_ENTROPY`].
+    pub fn is_high_entropy(&self) -> bool {
+        self.exceeds(HIGH_ENTROPY)
+    }
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Finding::UnknownChunk {
+                offset,
+                chunk_type,
+                private,
+                len,
+                ..
+            } => write!(
+                f,
+                "Unknown {} ancillary chunk {} at offset {}, {} bytes",
+                if *private { "private" } else { "public" },
+                chunk_type,
+                offset,
+                len
+            )?,
+            Finding::InvalidChunkType {
+                offset,
+                chunk_type,
+                len,
+                ..
+            } => write!(
+                f,
+                "Chunk with invalid type code \"{}\" at offset {}, {} bytes",
+                chunk_type, offset, len
+            )?,
+            Finding::AfterIend {
+                offset,
+                chunk_type,
+                len,
+                ..
+            } => write!(
+                f,
+                "{} chunk after IEND at offset {}, {} bytes",
+                chunk_type, offset, len
+            )?,
+            Finding::TrailingData { offset, len, .. } => write!(
+                f,
+                "{} bytes of trailing data after IEND at offset {}",
+                len, offset
+            )?,
+        }
+
+        write!(f, ", entropy {:.2} bits/byte", self.entropy())?;
+        if self.is_high_entropy() {
+            write!(f, " (likely compressed or encrypted)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything in a file that could hold hidden data, in the order it was encountered.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Analysis {
+    pub findings: Vec<Finding>,
+    /// Total number of bytes that could hold hidden data.
+    pub capacity: usize,
+}
+
+impl Analysis {
+    /// Checks if nothing at all could hold hidden data.
+    pub fn is_clean(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// The findings whose entropy exceeds `threshold` bits per byte, scaled to their size, i.e.
+    /// the ones likely to hold a payload. Pass [`HIGH_ENTROPY`] to only get compressed or encrypted
+    /// looking data.
+    pub fn likely_payloads(&self, threshold: f64) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(move |finding| finding.exceeds(threshold))
+    }
+}
+
+impl std::fmt::Display for Analysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_clean() {
+            return writeln!(f, "No hidden data found");
+        }
+
+        for finding in &self.findings {
+            writeln!(f, "{}", finding)?;
+        }
+        writeln!(f, "Up to {} bytes of hidden data", self.capacity)
+    }
+}
+
+/// Looks for places a payload could be hidden in: ancillary chunks of unknown types, chunks with
+/// invalid type codes, chunks after `IEND` and trailing bytes.
+///
+/// Crcs aren't checked. Fails if the signature is missing or the file is truncated before `IEND`;
+/// use [`crate::validate::check`] to find out why.
+pub fn analyze(bytes: &[u8]) -> Result<Analysis> {
+    let mut findings = Vec::new();
+    let mut offset = Png::STANDARD_HEADER.len();
+    let mut after_iend = false;
+
+    for chunk in ChunkRefs::new(bytes)? {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            // Whatever follows IEND doesn't have to be chunks.
+            Err(_) if after_iend => break,
+            Err(e) => return Err(e),
+        };
+
+        let chunk_type = chunk.chunk_type();
+        let len = chunk.data().len();
+        if after_iend {
+            findings.push(Finding::AfterIend {
+                offset,
+                chunk_type: chunk_type.to_string(),
+                len,
+                entropy: entropy(chunk.data()),
+            });
+        } else if !chunk_type.bytes_are_alphanumeric() {
+            findings.push(Finding::InvalidChunkType {
+                offset,
+                chunk_type: chunk_type.to_string(),
+                len,
+                entropy: entropy(chunk.data()),
+            });
+        } else if !chunk_type.is_critical() && !chunk_type.is_standard() {
+            findings.push(Finding::UnknownChunk {
+                offset,
+                chunk_type: chunk_type.to_string(),
+                private: !chunk_type.is_public(),
+                len,
+                entropy: entropy(chunk.data()),
+            });
+        }
+
+        after_iend |= *chunk_type == ChunkType::IEND;
+        offset += 12 + len;
+    }
+
+    if offset < bytes.len() {
+        findings.push(Finding::TrailingData {
+            offset,
+            len: bytes.len() - offset,
+            entropy: entropy(&bytes[offset..]),
+        });
+    }
+
+    let capacity = findings.iter().map(Finding::size).sum();
+    Ok(Analysis { findings, capacity })
+}
+
+/// Shannon entropy of `data` in bits per byte, from 0 for a single repeated value to 8 for
+/// uniformly random bytes.
+pub fn entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::png::tests::PNG_FILE;
+    use std::str::FromStr;
+
+    fn chunk_bytes(chunk_type: &str, data: &[u8]) -> Vec<u8> {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec()).as_bytes()
+    }
+
+    #[test]
+    fn test_clean_file() {
+        let analysis = analyze(&PNG_FILE).unwrap();
+        assert!(analysis.is_clean());
+        assert_eq!(analysis.capacity, 0);
+    }
+
+    #[test]
+    fn test_unknown_chunks() {
+        let mut png = Png::try_from(PNG_FILE.as_ref()).unwrap();
+        png.append_chunk(Chunk::new(ChunkType::TEXT, b"Comment\0Hi".to_vec()));
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"secret".to_vec(),
+        ));
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("RUSt").unwrap(),
+            b"critical".to_vec(),
+        ));
+
+        let analysis = analyze(&png.as_bytes()).unwrap();
+        assert_eq!(analysis.findings.len(), 1);
+        assert!(matches!(
+            &analysis.findings[0],
+            Finding::UnknownChunk { chunk_type, private: true, len: 6, .. } if chunk_type == "ruSt"
+        ));
+        assert_eq!(analysis.capacity, 6);
+    }
+
+    #[test]
+    fn test_data_after_iend() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend(chunk_bytes("IDAT", b"more image data"));
+        bytes.extend(b"junk");
+
+        let analysis = analyze(&bytes).unwrap();
+        assert_eq!(
+            analysis.findings,
+            vec![
+                Finding::AfterIend {
+                    offset: PNG_FILE.len(),
+                    chunk_type: "IDAT".to_string(),
+                    len: 15,
+                    entropy: entropy(b"more image data"),
+                },
+                Finding::TrailingData {
+                    offset: PNG_FILE.len() + 27,
+                    len: 4,
+                    entropy: 2.0,
+                },
+            ]
+        );
+        assert_eq!(analysis.capacity, 19);
+    }
+
+    #[test]
+    fn test_invalid_chunk_type() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[37..41].copy_from_slice(b"ID4T");
+
+        let analysis = analyze(&bytes).unwrap();
+        assert!(matches!(
+            &analysis.findings[..],
+            [Finding::InvalidChunkType { offset: 33, chunk_type, len: 12, .. }] if chunk_type == "ID4T"
+        ));
+    }
+
+    #[test]
+    fn test_likely_payloads() {
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        let mut png = Png::try_from(PNG_FILE.as_ref()).unwrap();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"This is where your secret message will be!".to_vec(),
+        ));
+        png.append_chunk(Chunk::new(ChunkType::from_str("ruSt").unwrap(), all_bytes));
+
+        let analysis = analyze(&png.as_bytes()).unwrap();
+        assert_eq!(analysis.findings.len(), 2);
+        let likely: Vec<&Finding> = analysis.likely_payloads(HIGH_ENTROPY).collect();
+        assert_eq!(likely, vec![&analysis.findings[1]]);
+        assert_eq!(analysis.likely_payloads(0.0).count(), 2);
+    }
+
+    #[test]
+    fn test_short_payloads() {
+        // Deterministic bytes that look random, too short to ever reach HIGH_ENTROPY.
+        let mut state = 0x2545f491u32;
+        let random: Vec<u8> = (0..150)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        assert!(entropy(&random) < HIGH_ENTROPY);
+
+        let mut png = Png::try_from(PNG_FILE.as_ref()).unwrap();
+        png.append_chunk(Chunk::new(ChunkType::from_str("ruSt").unwrap(), random));
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"short secret".to_vec(),
+        ));
+        let analysis = analyze(&png.as_bytes()).unwrap();
+        let likely: Vec<&Finding> = analysis.likely_payloads(HIGH_ENTROPY).collect();
+        assert_eq!(likely, vec![&analysis.findings[0]]);
+    }
+
+    #[test]
+    fn test_scaled_threshold() {
+        assert_eq!(scaled_threshold(HIGH_ENTROPY, 0), 0.0);
+        assert_eq!(scaled_threshold(HIGH_ENTROPY, 1), 0.0);
+        assert!(scaled_threshold(HIGH_ENTROPY, 62) < 62f64.log2());
+        assert!((scaled_threshold(HIGH_ENTROPY, 1 << 16) - HIGH_ENTROPY).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_damaged_file() {
+        assert!(analyze(&PNG_FILE[1..]).is_err());
+        assert!(analyze(&PNG_FILE[..40]).is_err());
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(b""), 0.0);
+        assert_eq!(entropy(b"aaaa"), 0.0);
+        assert_eq!(entropy(b"abab"), 1.0);
+
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(entropy(&all_bytes), 8.0);
+        assert!(entropy(b"This is where your secret message will be!") < HIGH_ENTROPY);
+    }
+}
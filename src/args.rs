@@ -1 +1,463 @@
+use std::path::PathBuf;
 
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use pngme_rs::analyze::HIGH_ENTROPY;
+use pngme_rs::payload::DEFAULT_MAX_CHUNK_SIZE;
+use pngme_rs::png::Position;
+
+/// Hide secret messages in PNG files.
+#[derive(Debug, Parser)]
+#[command(name = "pngme", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+    /// Accept chunks with a wrong crc or an invalid type code, printing a warning for each and
+    /// fixing the crc on write. `check` and `analyze` never stop at either.
+    #[arg(long, global = true)]
+    pub lenient: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Encode a message into a new chunk of the given type.
+    Encode(EncodeArgs),
+    /// Print the message stored in chunks of the given type, joining it if it was split.
+    Decode(DecodeArgs),
+    /// Remove the message stored in chunks of the given type: the first chunk, or every part if it
+    /// was split.
+    Remove(RemoveArgs),
+    /// Print all chunks of the file.
+    Print(PrintArgs),
+    /// Check the file for spec violations.
+    Check(CheckArgs),
+    /// Print the image header and a summary of the file's chunks.
+    Info(InfoArgs),
+    /// Search every PNG file below a directory for non-standard ancillary chunks.
+    Scan(ScanArgs),
+    /// Remove all ancillary chunks, e.g. to scrub metadata before sharing a file.
+    Strip(StripArgs),
+    /// Look for hidden data: unknown ancillary chunks and anything after IEND. Exits with an error
+    /// if any of it likely holds a payload, judged by its entropy.
+    Analyze(AnalyzeArgs),
+    /// Read an ASCII armor block: write out an armored payload, or insert an armored chunk into a
+    /// file.
+    Dearmor(DearmorArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct EncodeArgs {
+    pub file_path: PathBuf,
+    /// The chunk type, or the keyword when `--text-chunk` is given.
+    pub chunk_type: String,
+    #[arg(required_unless_present = "input_file")]
+    pub message: Option<String>,
+    /// Embed the contents of this file instead of a message.
+    #[arg(short, long, conflicts_with_all = ["message", "text_chunk"])]
+    pub input_file: Option<PathBuf>,
+    /// Split payloads larger than this many bytes across several chunks.
+    #[arg(long, default_value_t = DEFAULT_MAX_CHUNK_SIZE)]
+    pub max_chunk_size: usize,
+    /// Where to insert the new chunk: before-iend, after-ihdr, after-idat or a chunk index.
+    #[arg(long, default_value = "before-iend")]
+    pub position: Position,
+    /// Store the message as a standard textual chunk instead of a custom chunk type.
+    #[arg(long, value_enum, conflicts_with = "password")]
+    pub text_chunk: Option<TextChunkKind>,
+    /// Write the result to this file instead of overwriting the input file.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Encrypt the message with a key derived from this password.
+    #[arg(short, long)]
+    pub password: Option<String>,
+    /// Sign the payload with the Ed25519 private key in this PKCS#8 PEM file.
+    #[arg(long, value_name = "KEY_FILE", conflicts_with = "text_chunk")]
+    pub sign: Option<PathBuf>,
+    /// Compress the payload, with deflate unless another algorithm is given as `--compress=zstd`.
+    #[arg(
+        long,
+        value_enum,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "deflate",
+        conflicts_with = "text_chunk"
+    )]
+    pub compress: Option<CompressionKind>,
+}
+
+#[derive(Debug, Args)]
+pub struct DecodeArgs {
+    pub file_path: PathBuf,
+    pub chunk_type: String,
+    /// Decrypt a message that was encoded with `--password`.
+    #[arg(short, long)]
+    pub password: Option<String>,
+    /// Write the raw payload to this file instead of printing it as a message.
+    #[arg(short, long)]
+    pub output_file: Option<PathBuf>,
+    /// Fail unless the payload is signed by the Ed25519 public key in this PEM file.
+    #[arg(long, value_name = "PUBLIC_KEY_FILE")]
+    pub verify: Option<PathBuf>,
+    /// Treat the file path as a glob pattern, e.g. 'assets/**/*.png', and decode every match.
+    #[arg(long, conflicts_with = "output_file")]
+    pub glob: bool,
+    /// Decode every chunk of the type as a separate message instead of joining them into one
+    /// payload.
+    #[arg(long, conflicts_with_all = ["output_file", "glob"])]
+    pub all: bool,
+    /// Print the payload as an ASCII armor block instead of a message, see `dearmor`.
+    #[arg(long, conflicts_with_all = ["glob", "all"])]
+    pub armor: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RemoveArgs {
+    pub file_path: PathBuf,
+    pub chunk_type: String,
+}
+
+#[derive(Debug, Args)]
+pub struct PrintArgs {
+    pub file_path: PathBuf,
+    /// List the keywords and text of all tEXt, zTXt and iTXt chunks instead.
+    #[arg(long)]
+    pub text: bool,
+    /// Only list ancillary chunks.
+    #[arg(long)]
+    pub ancillary_only: bool,
+    /// Only list private chunks.
+    #[arg(long)]
+    pub private_only: bool,
+    /// Only list chunks that are safe to copy into an edited file.
+    #[arg(long)]
+    pub safe_to_copy: bool,
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct CheckArgs {
+    pub file_path: PathBuf,
+    /// Repair crcs, drop broken ancillary chunks and trailing data, and write the result to
+    /// `--output`.
+    #[arg(long, requires = "output")]
+    pub fix: bool,
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct AnalyzeArgs {
+    pub file_path: PathBuf,
+    /// Entropy in bits per byte above which a finding counts as a likely payload and makes the
+    /// command fail. Scaled down for findings too short to reach it. Pass 0 to fail on any finding.
+    #[arg(long, default_value_t = HIGH_ENTROPY)]
+    pub threshold: f64,
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct InfoArgs {
+    pub file_path: PathBuf,
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Args)]
+pub struct StripArgs {
+    pub file_path: PathBuf,
+    /// Comma-separated chunk types to keep, e.g. `tEXt,tIME`.
+    #[arg(long, value_delimiter = ',')]
+    pub keep: Vec<String>,
+    /// Write the result to this file instead of overwriting the input file.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct DearmorArgs {
+    /// The file holding the armor block, or `-` for stdin.
+    pub armor_file: PathBuf,
+    /// Insert the armored chunk before `IEND` of this file. Required for chunk blocks.
+    #[arg(long, value_name = "PNG_FILE")]
+    pub into: Option<PathBuf>,
+    /// Write the payload to this file instead of stdout, or the file with the inserted chunk
+    /// instead of overwriting the file given to `--into`.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ScanArgs {
+    pub dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable text.
+    Text,
+    /// Pretty-printed JSON, e.g. to pipe into `jq`.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CompressionKind {
+    Deflate,
+    Zstd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TextChunkKind {
+    /// Uncompressed Latin-1 text (tEXt).
+    Text,
+    /// Compressed Latin-1 text (zTXt).
+    Ztxt,
+    /// UTF-8 text (iTXt).
+    Itxt,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_encode() {
+        let cli = Cli::try_parse_from([
+            "pngme", "encode", "in.png", "ruSt", "message", "--output", "out.png",
+        ])
+        .unwrap();
+
+        match cli.command {
+            Command::Encode(args) => {
+                assert_eq!(args.file_path, PathBuf::from("in.png"));
+                assert_eq!(args.chunk_type, "ruSt");
+                assert_eq!(args.message.as_deref(), Some("message"));
+                assert_eq!(args.output, Some(PathBuf::from("out.png")));
+                assert_eq!(args.position, Position::BeforeIend);
+            }
+            _ => panic!("Expected encode command"),
+        }
+    }
+
+    #[test]
+    fn test_parse_glob() {
+        let cli =
+            Cli::try_parse_from(["pngme", "decode", "--glob", "assets/**/*.png", "ruSt"]).unwrap();
+        match cli.command {
+            Command::Decode(args) => {
+                assert!(args.glob);
+                assert_eq!(args.file_path, PathBuf::from("assets/**/*.png"));
+                assert_eq!(args.chunk_type, "ruSt");
+            }
+            _ => panic!("Expected decode command"),
+        }
+
+        let res = Cli::try_parse_from(["pngme", "decode", "--glob", "*.png", "ruSt", "-o", "out"]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_parse_print_filters() {
+        let cli = Cli::try_parse_from([
+            "pngme",
+            "print",
+            "in.png",
+            "--ancillary-only",
+            "--safe-to-copy",
+        ])
+        .unwrap();
+        match cli.command {
+            Command::Print(args) => {
+                assert!(args.ancillary_only);
+                assert!(!args.private_only);
+                assert!(args.safe_to_copy);
+            }
+            _ => panic!("Expected print command"),
+        }
+
+        let res = Cli::try_parse_from(["pngme", "decode", "--all", "--glob", "*.png", "ruSt"]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_parse_strip() {
+        let cli = Cli::try_parse_from(["pngme", "strip", "in.png", "--keep", "tEXt,tIME"]).unwrap();
+        match cli.command {
+            Command::Strip(args) => {
+                assert_eq!(args.keep, vec!["tEXt", "tIME"]);
+                assert_eq!(args.output, None);
+            }
+            _ => panic!("Expected strip command"),
+        }
+    }
+
+    #[test]
+    fn test_parse_dearmor() {
+        let cli = Cli::try_parse_from(["pngme", "dearmor", "-", "--into", "in.png"]).unwrap();
+        match cli.command {
+            Command::Dearmor(args) => {
+                assert_eq!(args.armor_file, PathBuf::from("-"));
+                assert_eq!(args.into, Some(PathBuf::from("in.png")));
+                assert_eq!(args.output, None);
+            }
+            _ => panic!("Expected dearmor command"),
+        }
+
+        let cli = Cli::try_parse_from(["pngme", "decode", "in.png", "ruSt", "--armor"]).unwrap();
+        match cli.command {
+            Command::Decode(args) => assert!(args.armor),
+            _ => panic!("Expected decode command"),
+        }
+        assert!(
+            Cli::try_parse_from(["pngme", "decode", "in.png", "ruSt", "--armor", "--all"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        let cli = Cli::try_parse_from(["pngme", "decode", "in.png", "ruSt", "--lenient"]).unwrap();
+        assert!(cli.lenient);
+
+        let cli = Cli::try_parse_from(["pngme", "decode", "in.png", "ruSt"]).unwrap();
+        assert!(!cli.lenient);
+    }
+
+    #[test]
+    fn test_parse_format() {
+        let cli = Cli::try_parse_from(["pngme", "info", "in.png", "--format", "json"]).unwrap();
+        match cli.command {
+            Command::Info(args) => assert_eq!(args.format, OutputFormat::Json),
+            _ => panic!("Expected info command"),
+        }
+
+        assert!(Cli::try_parse_from(["pngme", "print", "in.png", "--format", "yaml"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_compress() {
+        let parse = |args: &[&str]| match Cli::try_parse_from(args).unwrap().command {
+            Command::Encode(args) => args.compress,
+            _ => panic!("Expected encode command"),
+        };
+
+        assert_eq!(parse(&["pngme", "encode", "in.png", "ruSt", "msg"]), None);
+        assert_eq!(
+            parse(&["pngme", "encode", "--compress", "in.png", "ruSt", "msg"]),
+            Some(CompressionKind::Deflate)
+        );
+        assert_eq!(
+            parse(&[
+                "pngme",
+                "encode",
+                "in.png",
+                "ruSt",
+                "msg",
+                "--compress=zstd"
+            ]),
+            Some(CompressionKind::Zstd)
+        );
+    }
+
+    #[test]
+    fn test_parse_text_chunk() {
+        let cli = Cli::try_parse_from([
+            "pngme",
+            "encode",
+            "--text-chunk",
+            "ztxt",
+            "in.png",
+            "Comment",
+            "message",
+        ])
+        .unwrap();
+
+        match cli.command {
+            Command::Encode(args) => assert_eq!(args.text_chunk, Some(TextChunkKind::Ztxt)),
+            _ => panic!("Expected encode command"),
+        }
+
+        let res = Cli::try_parse_from([
+            "pngme",
+            "encode",
+            "--text-chunk",
+            "text",
+            "--password",
+            "hunter2",
+            "in.png",
+            "Comment",
+            "message",
+        ]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_parse_input_file() {
+        let cli = Cli::try_parse_from(["pngme", "encode", "in.png", "ruSt", "-i", "payload.bin"])
+            .unwrap();
+
+        match cli.command {
+            Command::Encode(args) => {
+                assert_eq!(args.message, None);
+                assert_eq!(args.input_file, Some(PathBuf::from("payload.bin")));
+                assert_eq!(args.max_chunk_size, DEFAULT_MAX_CHUNK_SIZE);
+            }
+            _ => panic!("Expected encode command"),
+        }
+
+        let res = Cli::try_parse_from([
+            "pngme",
+            "encode",
+            "in.png",
+            "ruSt",
+            "message",
+            "-i",
+            "payload.bin",
+        ]);
+        assert!(res.is_err());
+        assert!(Cli::try_parse_from(["pngme", "encode", "in.png", "ruSt"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_position() {
+        let cli = Cli::try_parse_from([
+            "pngme",
+            "encode",
+            "in.png",
+            "ruSt",
+            "message",
+            "--position",
+            "after-ihdr",
+        ])
+        .unwrap();
+
+        match cli.command {
+            Command::Encode(args) => assert_eq!(args.position, Position::AfterIhdr),
+            _ => panic!("Expected encode command"),
+        }
+
+        let res = Cli::try_parse_from([
+            "pngme",
+            "encode",
+            "in.png",
+            "ruSt",
+            "message",
+            "--position",
+            "middle",
+        ]);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_parse_missing_args() {
+        assert!(Cli::try_parse_from(["pngme", "decode", "in.png"]).is_err());
+    }
+}
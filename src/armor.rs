@@ -0,0 +1,128 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use crate::error::PngError;
+use crate::Result;
+
+pub const CHUNK_LABEL: &str = "PNGME CHUNK";
+pub const PAYLOAD_LABEL: &str = "PNGME PAYLOAD";
+
+const LINE_WIDTH: usize = 64;
+
+/// Wraps `bytes` in an ASCII armor block, similar to PGP armor: a header line naming `label`, the
+/// base64 body wrapped at 64 columns, a `=` line holding the base64-encoded CRC-32 of the bytes
+/// and a footer line.
+pub fn armor(label: &str, bytes: &[u8]) -> String {
+    let body = STANDARD.encode(bytes);
+    let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(bytes);
+
+    let mut s = format!("-----BEGIN {}-----\n", label);
+    for line in body.as_bytes().chunks(LINE_WIDTH) {
+        // Base64 output is pure ASCII, so splitting it at any byte is fine.
+        s.push_str(std::str::from_utf8(line).expect("Base64 is valid utf-8"));
+        s.push('\n');
+    }
+    s.push('=');
+    s.push_str(&STANDARD.encode(crc.to_be_bytes()));
+    s.push('\n');
+    s.push_str(&format!("-----END {}-----\n", label));
+    s
+}
+
+/// Parses an armor block produced by [`armor`], returning its label and the decoded bytes. Any text
+/// surrounding the block is ignored. Fails if the block is incomplete, the header and footer labels
+/// differ, or the checksum doesn't match the body.
+pub fn dearmor(s: &str) -> Result<(String, Vec<u8>)> {
+    let mut lines = s
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| !line.starts_with("-----BEGIN "));
+
+    let label = lines
+        .next()
+        .and_then(|line| line.strip_prefix("-----BEGIN "))
+        .and_then(|line| line.strip_suffix("-----"))
+        .ok_or_else(|| PngError::InvalidArmor("No header found".to_string()))?
+        .to_string();
+    let footer = format!("-----END {}-----", label);
+
+    let mut body = String::new();
+    let mut crc = None;
+    let mut found_footer = false;
+    for line in lines.by_ref() {
+        if line == footer {
+            found_footer = true;
+            break;
+        }
+
+        match line.strip_prefix('=') {
+            Some(checksum) => crc = Some(checksum.to_string()),
+            None => body.push_str(line),
+        }
+    }
+
+    if !found_footer {
+        return Err(PngError::InvalidArmor(format!(
+            "Missing footer for {}",
+            label
+        )));
+    }
+
+    let bytes = STANDARD.decode(body)?;
+
+    let crc = crc.ok_or_else(|| PngError::InvalidArmor("Missing checksum".to_string()))?;
+    let crc: [u8; 4] = STANDARD
+        .decode(crc)?
+        .try_into()
+        .map_err(|_| PngError::InvalidArmor("Checksum must be 4 bytes long".to_string()))?;
+    let expected = u32::from_be_bytes(crc);
+    let actual = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&bytes);
+    if expected != actual {
+        return Err(PngError::CrcMismatch { expected, actual });
+    }
+
+    Ok((label, bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_armor_round_trip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let armored = armor(PAYLOAD_LABEL, &bytes);
+
+        assert!(armored.starts_with("-----BEGIN PNGME PAYLOAD-----\n"));
+        assert!(armored.ends_with("-----END PNGME PAYLOAD-----\n"));
+        assert!(armored.lines().all(|line| line.len() <= LINE_WIDTH));
+
+        let (label, actual) = dearmor(&armored).unwrap();
+        assert_eq!(label, PAYLOAD_LABEL);
+        assert_eq!(actual, bytes);
+    }
+
+    #[test]
+    fn test_dearmor_ignores_surrounding_text() {
+        let armored = format!(
+            "Hi, see below.\n\n{}\nCheers\n",
+            armor(CHUNK_LABEL, b"RuSt")
+        );
+        let (label, actual) = dearmor(&armored).unwrap();
+        assert_eq!(label, CHUNK_LABEL);
+        assert_eq!(actual, b"RuSt");
+    }
+
+    #[test]
+    fn test_dearmor_invalid() {
+        let armored = armor(PAYLOAD_LABEL, b"This is where your secret message will be!");
+
+        let tampered = armored.replacen("VGhp", "VGhq", 1);
+        assert!(dearmor(&tampered).is_err());
+
+        let truncated: String = armored.lines().take(2).collect::<Vec<_>>().join("\n");
+        assert!(dearmor(&truncated).is_err());
+
+        assert!(dearmor("no armor here").is_err());
+    }
+}
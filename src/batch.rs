@@ -0,0 +1,90 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use glob::MatchOptions;
+use rayon::prelude::*;
+
+use crate::cli_error::Result;
+
+/// `.PNG` files match a `*.png` pattern, too.
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: false,
+    require_literal_separator: false,
+    require_literal_leading_dot: false,
+};
+
+/// The outcome of running an operation on one file of a batch.
+pub struct FileResult<T> {
+    pub path: PathBuf,
+    pub result: Result<T>,
+}
+
+/// Runs `f` on every file matching `pattern`, in parallel. Results come back sorted by path. A
+/// failure on one file, including not being able to list it, is recorded in its result and
+/// doesn't stop the others; only an invalid pattern fails the whole batch.
+pub fn process<T, F>(pattern: &str, f: F) -> Result<Vec<FileResult<T>>>
+where
+    T: Send,
+    F: Fn(&Path) -> Result<T> + Sync,
+{
+    let entries: Vec<_> = glob::glob_with(pattern, MATCH_OPTIONS)?.collect();
+
+    Ok(entries
+        .into_par_iter()
+        .map(|entry| match entry {
+            Ok(path) => {
+                let result = f(&path);
+                FileResult { path, result }
+            }
+            Err(e) => FileResult {
+                path: e.path().to_path_buf(),
+                result: Err(io::Error::from(e).into()),
+            },
+        })
+        .collect())
+}
+
+/// Builds a pattern matching every PNG file below `dir`.
+pub fn png_pattern(dir: &Path) -> String {
+    let dir = glob::Pattern::escape(&dir.to_string_lossy());
+    format!("{}/**/*.png", dir.trim_end_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli_error::CliError;
+    use pngme_rs::error::PngError;
+    use std::fs;
+
+    #[test]
+    fn test_process() {
+        let dir = std::env::temp_dir().join(format!("pngme-batch-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("a.png"), "a").unwrap();
+        fs::write(dir.join("nested/b.PNG"), "bb").unwrap();
+        fs::write(dir.join("c.txt"), "ccc").unwrap();
+
+        let results = process(&png_pattern(&dir), |path| match fs::read(path)?.len() {
+            1 => Err(PngError::UnexpectedEof.into()),
+            len => Ok(len),
+        })
+        .unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].path, dir.join("a.png"));
+        assert!(results[0].result.is_err());
+        assert_eq!(results[1].path, dir.join("nested/b.PNG"));
+        assert_eq!(*results[1].result.as_ref().unwrap(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(matches!(
+            process("[", |_| Ok(())),
+            Err(CliError::InvalidGlob(_))
+        ));
+    }
+}
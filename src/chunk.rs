@@ -1,66 +1,169 @@
+use std::borrow::Cow;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
 use crc::{Crc, CRC_32_ISO_HDLC};
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
 
+use crate::armor;
 use crate::chunk_type::ChunkType;
+use crate::compression::Compression;
+use crate::envelope::{self, Envelope};
 
+use crate::error::PngError;
 use crate::{Error, Result};
 
+pub(crate) const CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
 pub struct Chunk {
     chunk_type: ChunkType,
     data: Vec<u8>,
 }
 
 impl Chunk {
-    fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
+    pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Chunk {
         Self { chunk_type, data }
     }
 
-    fn length(&self) -> u32 {
+    pub fn length(&self) -> u32 {
         // The length is the number of bytes in the data field.
         self.data.len() as u32
     }
 
-    fn chunk_type(&self) -> &ChunkType {
+    pub fn chunk_type(&self) -> &ChunkType {
         &self.chunk_type
     }
 
-    fn data(&self) -> &[u8] {
+    pub fn data(&self) -> &[u8] {
         &self.data
     }
 
-    fn crc(&self) -> u32 {
+    pub fn crc(&self) -> u32 {
         Chunk::compute_crc(&self.chunk_type, &self.data)
     }
 
     /// Returns the data stored in this chunk as a `String`. This function will return an error
     /// if the stored data is not valid UTF-8.
-    fn data_as_string(&self) -> Result<String> {
-        Ok(String::from_utf8(self.data.clone())?)
+    pub fn data_as_string(&self) -> Result<String> {
+        Ok(self.data_as_str()?.to_owned())
     }
 
-    fn as_bytes(&self) -> Vec<u8> {
-        let res = self
-            .length()
-            .to_be_bytes()
-            .iter()
-            .cloned()
-            .chain(self.chunk_type.bytes())
-            .chain(self.data.iter().cloned())
-            .chain(self.crc().to_be_bytes().iter().cloned())
-            .collect::<Vec<u8>>();
+    /// Returns the data stored in this chunk as a `&str` without copying it. This function will
+    /// return an error if the stored data is not valid UTF-8.
+    pub fn data_as_str(&self) -> Result<&str> {
+        Ok(std::str::from_utf8(&self.data)?)
+    }
+
+    /// Returns the data stored in this chunk decoded as Latin-1 (ISO 8859-1), the encoding the spec
+    /// mandates for `tEXt` and `zTXt` chunks. Every byte maps to a code point, so this can't fail.
+    /// Pure ASCII data is borrowed rather than copied.
+    pub fn data_as_latin1(&self) -> Cow<'_, str> {
+        Chunk::decode_latin1(&self.data)
+    }
+
+    /// Returns the data stored in this chunk as UTF-8, replacing invalid sequences with `U+FFFD`.
+    /// Valid data is borrowed rather than copied.
+    pub fn data_as_string_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.data)
+    }
+
+    /// Consumes the chunk and returns its data.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Consumes the chunk and returns its data as a `String` without copying it. Fails if the data
+    /// is not valid UTF-8.
+    pub fn into_string(self) -> Result<String> {
+        Ok(String::from_utf8(self.data)?)
+    }
+
+    /// Consumes the chunk and returns its type and data.
+    pub fn into_parts(self) -> (ChunkType, Vec<u8>) {
+        (self.chunk_type, self.data)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::with_capacity(self.data.len() + 12);
+        res.extend_from_slice(&self.length().to_be_bytes());
+        res.extend_from_slice(&self.chunk_type.bytes());
+        res.extend_from_slice(&self.data);
+        res.extend_from_slice(&self.crc().to_be_bytes());
         res
     }
 }
 
 impl Chunk {
-    fn compute_crc(chunk_type: &ChunkType, data: &Vec<u8>) -> u32 {
-        let d: Vec<u8> = chunk_type
-            .bytes()
-            .iter()
-            .cloned()
-            .chain(data.iter().cloned())
-            .collect();
+    /// Feeds type code and data into the crc one after the other, so they never have to be copied
+    /// into a single buffer.
+    pub(crate) fn compute_crc(chunk_type: &ChunkType, data: &[u8]) -> u32 {
+        let mut digest = CRC.digest();
+        digest.update(&chunk_type.bytes());
+        digest.update(data);
+        digest.finalize()
+    }
 
-        Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(&d[..])
+    /// Returns the 4 bytes starting at `offset`, failing if `value` is too short.
+    pub(crate) fn read_4_bytes(value: &[u8], offset: usize) -> Result<[u8; 4]> {
+        value
+            .get(offset..offset + 4)
+            .and_then(|b| b.try_into().ok())
+            .ok_or(PngError::UnexpectedEof)
+    }
+
+    /// Decodes Latin-1 bytes, borrowing them if they are pure ASCII.
+    pub(crate) fn decode_latin1(bytes: &[u8]) -> Cow<'_, str> {
+        match std::str::from_utf8(bytes) {
+            Ok(s) if bytes.is_ascii() => Cow::Borrowed(s),
+            _ => Cow::Owned(bytes.iter().map(|&b| b as char).collect()),
+        }
+    }
+}
+
+impl Chunk {
+    /// Creates a chunk holding `data` compressed with the given algorithm, in a payload envelope
+    /// that records the algorithm.
+    pub fn new_compressed(
+        chunk_type: ChunkType,
+        data: &[u8],
+        compression: Compression,
+    ) -> Result<Chunk> {
+        let compressed = compression.compress(data)?;
+        let data = envelope::seal(compressed, Some(compression), false, None);
+        Ok(Chunk::new(chunk_type, data))
+    }
+
+    /// Returns the data, decompressed if the chunk holds a compressed payload envelope. Data
+    /// without an envelope is borrowed as-is. Encrypted payloads have to be decrypted before they
+    /// can be decompressed, so this fails for them.
+    pub fn decompressed_data(&self) -> Result<Cow<'_, [u8]>> {
+        let envelope = Envelope::open(&self.data)?;
+        match envelope.compression() {
+            Some(compression) => Ok(Cow::Owned(compression.decompress(envelope.body())?)),
+            None => Ok(Cow::Borrowed(envelope.body())),
+        }
+    }
+}
+
+impl Chunk {
+    /// Returns the chunk's bytes wrapped in an ASCII armor block, suitable for pasting into email
+    /// or chat.
+    pub fn to_armor(&self) -> String {
+        armor::armor(armor::CHUNK_LABEL, &self.as_bytes())
+    }
+
+    /// Parses a chunk from an ASCII armor block produced by [`Chunk::to_armor`].
+    pub fn from_armor(s: &str) -> Result<Chunk> {
+        let (label, bytes) = armor::dearmor(s)?;
+        if label != armor::CHUNK_LABEL {
+            return Err(PngError::InvalidArmor(format!(
+                "Expected a {} block, got {}",
+                armor::CHUNK_LABEL,
+                label
+            )));
+        }
+
+        Chunk::try_from(bytes.as_ref())
     }
 }
 
@@ -70,47 +173,51 @@ impl TryFrom<&[u8]> for Chunk {
     /// Parses the first 4 bytes as the length of the supplied data, the next
     /// 4 bytes as the chunk type. The next bytes up until the last 4 bytes to the end are parsed as
     /// the data, the last 4 bytes will be parsed as the crc.
-    /// Fails if specified length and actual data's length or the provided and computed crc don't match.
+    /// Fails if the input is shorter than a chunk with no data, or if specified length and actual
+    /// data's length or the provided and computed crc don't match.
     fn try_from(value: &[u8]) -> Result<Self> {
+        if value.len() < 12 {
+            return Err(PngError::UnexpectedEof);
+        }
+
         // First 4 bytes (one u32) is the length.
-        let b_len: [u8; 4] = value[..4]
-            .to_vec()
-            .try_into()
-            .expect("Failed to parse length");
+        let b_len = Chunk::read_4_bytes(value, 0)?;
         let be_len = u32::from_be_bytes(b_len);
         let le_len = u32::from_le_bytes(b_len);
 
         // Next 4 byes is the type.
-        let b_type: [u8; 4] = value[4..8]
-            .to_vec()
-            .try_into()
-            .expect("Failed to parse chunk type code");
-        let chunk_type = ChunkType::try_from(b_type)?;
+        let chunk_type = ChunkType::try_from(Chunk::read_4_bytes(value, 4)?)?;
 
         let offset = value.len() - 4;
 
-        // Next n bytes is the data.
-        let data: Vec<u8> = value[8..offset].to_vec();
+        // Next n bytes is the data. It's only copied once both checks below have passed.
+        let data = &value[8..offset];
 
         // Last 4 bytes (one u32) is the crc.
-        let b_crc: [u8; 4] = value[offset..]
-            .to_vec()
-            .try_into()
-            .expect("Failed to parse crc");
+        let b_crc = Chunk::read_4_bytes(value, offset)?;
         let be_crc = u32::from_be_bytes(b_crc);
         let le_crc = u32::from_le_bytes(b_crc);
 
         let len = data.len() as u32;
         if !(len == be_len || len == le_len) {
-            return Err("Data does not have the specified length".into());
+            return Err(PngError::LengthMismatch {
+                expected: be_len,
+                actual: len,
+            });
         }
 
-        let crc = Chunk::compute_crc(&chunk_type, &data);
+        let crc = Chunk::compute_crc(&chunk_type, data);
         if !(crc == be_crc || crc == le_crc) {
-            return Err("Data does not match provided crc".into());
+            return Err(PngError::CrcMismatch {
+                expected: be_crc,
+                actual: crc,
+            });
         }
 
-        Ok(Self { chunk_type, data })
+        Ok(Self {
+            chunk_type,
+            data: data.to_vec(),
+        })
     }
 }
 
@@ -131,6 +238,18 @@ impl std::fmt::Display for Chunk {
     }
 }
 
+/// Serialized as its type, length, crc and the data encoded as base64.
+impl Serialize for Chunk {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Chunk", 4)?;
+        state.serialize_field("type", &self.chunk_type)?;
+        state.serialize_field("length", &self.length())?;
+        state.serialize_field("crc", &self.crc())?;
+        state.serialize_field("data", &STANDARD.encode(&self.data))?;
+        state.end()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -186,12 +305,101 @@ mod tests {
         assert_eq!(chunk_string, expected_chunk_string);
     }
 
+    #[test]
+    fn test_chunk_str() {
+        let chunk = testing_chunk();
+        assert_eq!(
+            chunk.data_as_str().unwrap(),
+            "This is where your secret message will be!"
+        );
+
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let chunk = Chunk::new(chunk_type, vec![0xff, 0xfe]);
+        assert!(chunk.data_as_str().is_err());
+    }
+
+    #[test]
+    fn test_chunk_latin1() {
+        let chunk_type = ChunkType::TEXT;
+        // "Café" in Latin-1, which is not valid UTF-8.
+        let chunk = Chunk::new(chunk_type, vec![0x43, 0x61, 0x66, 0xe9]);
+        assert!(chunk.data_as_string().is_err());
+        assert_eq!(chunk.data_as_latin1(), "Café");
+    }
+
+    #[test]
+    fn test_chunk_string_lossy() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let chunk = Chunk::new(chunk_type, vec![0x43, 0x61, 0x66, 0xe9]);
+        assert_eq!(chunk.data_as_string_lossy(), "Caf\u{FFFD}");
+    }
+
+    #[test]
+    fn test_chunk_accessors_borrow_valid_data() {
+        let chunk = testing_chunk();
+        assert!(matches!(chunk.data_as_latin1(), Cow::Borrowed(_)));
+        assert!(matches!(chunk.data_as_string_lossy(), Cow::Borrowed(_)));
+        assert_eq!(
+            chunk.into_string().unwrap(),
+            "This is where your secret message will be!"
+        );
+    }
+
+    #[test]
+    fn test_chunk_into_data() {
+        let chunk = testing_chunk();
+        let expected = chunk.data().to_vec();
+        assert_eq!(chunk.into_data(), expected);
+    }
+
+    #[test]
+    fn test_chunk_into_parts() {
+        let chunk = testing_chunk();
+        let (chunk_type, data) = chunk.into_parts();
+        assert_eq!(chunk_type.to_string(), String::from("RuSt"));
+        assert_eq!(
+            data,
+            "This is where your secret message will be!".as_bytes()
+        );
+    }
+
     #[test]
     fn test_chunk_crc() {
         let chunk = testing_chunk();
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_chunk_serialize() {
+        let json = serde_json::to_value(testing_chunk()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "type": "RuSt",
+                "length": 42,
+                "crc": 2882656334u32,
+                "data": "VGhpcyBpcyB3aGVyZSB5b3VyIHNlY3JldCBtZXNzYWdlIHdpbGwgYmUh",
+            })
+        );
+    }
+
+    #[test]
+    fn test_compressed_chunk() {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let message = "This is where your secret message will be! ".repeat(20);
+        let chunk =
+            Chunk::new_compressed(chunk_type, message.as_bytes(), Compression::Deflate).unwrap();
+        assert!(chunk.data().len() < message.len());
+        assert_eq!(*chunk.decompressed_data().unwrap(), *message.as_bytes());
+
+        // Plain data is passed through without copying.
+        let chunk = testing_chunk();
+        assert!(matches!(
+            chunk.decompressed_data().unwrap(),
+            Cow::Borrowed(_)
+        ));
+    }
+
     #[test]
     fn test_valid_chunk_from_bytes() {
         let data_length: u32 = 42;
@@ -240,6 +448,40 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_invalid_chunk_errors() {
+        let mut bytes = testing_chunk().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert!(matches!(
+            Chunk::try_from(bytes.as_ref()),
+            Err(PngError::CrcMismatch {
+                actual: 2882656334,
+                ..
+            })
+        ));
+
+        let bytes = testing_chunk().as_bytes();
+        assert!(matches!(
+            Chunk::try_from(&bytes[..bytes.len() - 1]),
+            Err(PngError::LengthMismatch {
+                expected: 42,
+                actual: 41
+            })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_chunk_from_bytes() {
+        for len in 0..12 {
+            let bytes = vec![0; len];
+            assert!(matches!(
+                Chunk::try_from(bytes.as_ref()),
+                Err(PngError::UnexpectedEof)
+            ));
+        }
+    }
+
     #[test]
     pub fn valid_chunk_to_bytes() {
         let data_length: u32 = 42;
@@ -261,6 +503,19 @@ mod tests {
         assert_eq!(chunk_data, chunk.as_bytes());
     }
 
+    #[test]
+    pub fn chunk_armor_round_trip() {
+        let chunk = testing_chunk();
+        let armored = chunk.to_armor();
+        assert!(armored.starts_with("-----BEGIN PNGME CHUNK-----"));
+
+        let actual = Chunk::from_armor(&armored).unwrap();
+        assert_eq!(actual.as_bytes(), chunk.as_bytes());
+
+        let payload = armor::armor(armor::PAYLOAD_LABEL, &chunk.as_bytes());
+        assert!(Chunk::from_armor(&payload).is_err());
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
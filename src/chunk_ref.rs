@@ -0,0 +1,195 @@
+use std::borrow::Cow;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+use crate::error::PngError;
+use crate::{Error, Result};
+
+/// A chunk borrowed from a file held in memory, e.g. read with `fs::read` or memory-mapped. Unlike
+/// [`Chunk`] it never copies the data.
+pub struct ChunkRef<'a> {
+    chunk_type: ChunkType,
+    data: &'a [u8],
+    crc: u32,
+}
+
+impl<'a> ChunkRef<'a> {
+    pub fn length(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// The crc as stored in the file. Use [`ChunkRef::is_crc_valid`] to check it against the data.
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
+
+    pub fn is_crc_valid(&self) -> bool {
+        Chunk::compute_crc(&self.chunk_type, self.data) == self.crc
+    }
+
+    /// Returns the data as a `&str` borrowed from the file. Fails if it is not valid UTF-8.
+    pub fn data_as_str(&self) -> Result<&'a str> {
+        Ok(std::str::from_utf8(self.data)?)
+    }
+
+    /// Returns the data as UTF-8, replacing invalid sequences with `U+FFFD`. Valid data is
+    /// borrowed rather than copied.
+    pub fn data_as_string_lossy(&self) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.data)
+    }
+
+    /// Copies the data into an owned [`Chunk`].
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk::new(self.chunk_type, self.data.to_vec())
+    }
+
+    /// Parses the chunk at the start of `bytes` and returns it along with the bytes that follow.
+    fn parse(bytes: &'a [u8]) -> Result<(ChunkRef<'a>, &'a [u8])> {
+        let len = u32::from_be_bytes(Chunk::read_4_bytes(bytes, 0)?) as usize;
+        let chunk_type = ChunkType::new_unchecked(Chunk::read_4_bytes(bytes, 4)?);
+
+        let end = 8usize
+            .checked_add(len)
+            .filter(|&end| end <= bytes.len() - 4)
+            .ok_or(PngError::UnexpectedEof)?;
+        let crc = u32::from_be_bytes(Chunk::read_4_bytes(bytes, end)?);
+
+        let chunk = Self {
+            chunk_type,
+            data: &bytes[8..end],
+            crc,
+        };
+        Ok((chunk, &bytes[end + 4..]))
+    }
+}
+
+/// Parses exactly one chunk, without checking its crc.
+impl<'a> TryFrom<&'a [u8]> for ChunkRef<'a> {
+    type Error = Error;
+
+    fn try_from(value: &'a [u8]) -> Result<Self> {
+        let (chunk, rest) = ChunkRef::parse(value)?;
+        match rest.is_empty() {
+            true => Ok(chunk),
+            false => Err(PngError::LengthMismatch {
+                expected: chunk.length(),
+                actual: (value.len() - 12) as u32,
+            }),
+        }
+    }
+}
+
+/// Iterates over the chunks of a PNG file held in memory, borrowing each chunk's data from it.
+/// Crcs aren't checked, see [`ChunkRef::is_crc_valid`], and neither are type codes, so a chunk
+/// with a bad one can be reported instead of ending the iteration.
+pub struct ChunkRefs<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> ChunkRefs<'a> {
+    /// Fails if `file` doesn't start with the PNG signature.
+    pub fn new(file: &'a [u8]) -> Result<ChunkRefs<'a>> {
+        let rest = file
+            .strip_prefix(&Png::STANDARD_HEADER)
+            .ok_or(PngError::InvalidSignature)?;
+        Ok(Self { rest })
+    }
+}
+
+impl<'a> Iterator for ChunkRefs<'a> {
+    type Item = Result<ChunkRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        match ChunkRef::parse(self.rest) {
+            Ok((chunk, rest)) => {
+                self.rest = rest;
+                Some(Ok(chunk))
+            }
+            Err(e) => {
+                // Stop after the first error, there is no telling where the next chunk starts.
+                self.rest = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::PNG_FILE;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_chunk_refs() {
+        let chunks: Vec<ChunkRef> = ChunkRefs::new(&PNG_FILE)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        let types: Vec<String> = chunks.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, vec!["IHDR", "IDAT", "IEND"]);
+        assert!(chunks.iter().all(|c| c.is_crc_valid()));
+
+        // The data points into the file instead of a copy.
+        assert_eq!(chunks[0].data().as_ptr(), PNG_FILE[16..].as_ptr());
+    }
+
+    #[test]
+    fn test_chunk_ref_round_trip() {
+        let chunk = Chunk::new(
+            ChunkType::from_str("RuSt").unwrap(),
+            b"This is where your secret message will be!".to_vec(),
+        );
+        let bytes = chunk.as_bytes();
+
+        let chunk_ref = ChunkRef::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(chunk_ref.crc(), chunk.crc());
+        assert_eq!(
+            chunk_ref.data_as_str().unwrap(),
+            "This is where your secret message will be!"
+        );
+        assert_eq!(chunk_ref.to_chunk().as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_invalid_chunk_refs() {
+        assert!(matches!(
+            ChunkRefs::new(&PNG_FILE[1..]),
+            Err(PngError::InvalidSignature)
+        ));
+
+        let mut refs = ChunkRefs::new(&PNG_FILE[..PNG_FILE.len() - 1]).unwrap();
+        assert!(refs.by_ref().take(2).all(|c| c.is_ok()));
+        assert!(matches!(refs.next(), Some(Err(PngError::UnexpectedEof))));
+        assert!(refs.next().is_none());
+
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[56] ^= 1;
+        let chunks: Vec<ChunkRef> = ChunkRefs::new(&bytes)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert!(!chunks[1].is_crc_valid());
+
+        bytes[37..41].copy_from_slice(b"ID4T");
+        let chunks: Vec<ChunkRef> = ChunkRefs::new(&bytes)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert!(!chunks[1].chunk_type().bytes_are_alphanumeric());
+    }
+}
@@ -1,11 +1,95 @@
+use serde::{Serialize, Serializer};
+
+use crate::error::PngError;
 use crate::{Error, Result};
 
 // The (zero-based indexed) 5th bit switches an ASCII character from lower to upper case.
 const ASCI_UPPER: u8 = 0b0010_0000;
 
-#[derive(Debug, Eq, PartialEq)]
+/// Chunk types defined by the PNG spec and its registered public extensions, with a short
+/// description of each.
+const KNOWN_TYPES: [(ChunkType, &str); 33] = [
+    (ChunkType::IHDR, "Image header"),
+    (ChunkType::PLTE, "Palette"),
+    (ChunkType::IDAT, "Image data"),
+    (ChunkType::IEND, "Image trailer"),
+    (ChunkType::ACTL, "Animation control"),
+    (ChunkType::CHRM, "Primary chromaticities and white point"),
+    (ChunkType::CICP, "Coding-independent code points"),
+    (ChunkType::GAMA, "Image gamma"),
+    (ChunkType::ICCP, "Embedded ICC profile"),
+    (ChunkType::MDCV, "Mastering display color volume"),
+    (ChunkType::CLLI, "Content light level information"),
+    (ChunkType::SBIT, "Significant bits"),
+    (ChunkType::SRGB, "Standard RGB color space"),
+    (ChunkType::BKGD, "Background color"),
+    (ChunkType::HIST, "Image histogram"),
+    (ChunkType::TRNS, "Transparency"),
+    (ChunkType::EXIF, "Exif metadata"),
+    (ChunkType::FCTL, "Frame control"),
+    (ChunkType::PHYS, "Physical pixel dimensions"),
+    (ChunkType::SPLT, "Suggested palette"),
+    (ChunkType::FDAT, "Frame data"),
+    (ChunkType::TIME, "Last modification time"),
+    (ChunkType::ITXT, "International textual data"),
+    (ChunkType::TEXT, "Textual data"),
+    (ChunkType::ZTXT, "Compressed textual data"),
+    (ChunkType::OFFS, "Image offset"),
+    (ChunkType::PCAL, "Pixel calibration"),
+    (ChunkType::SCAL, "Physical scale"),
+    (ChunkType::GIFG, "GIF graphic control extension"),
+    (ChunkType::GIFT, "GIF plain text extension"),
+    (ChunkType::GIFX, "GIF application extension"),
+    (ChunkType::STER, "Stereo image indicator"),
+    (ChunkType::DSIG, "Digital signature"),
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct ChunkType([u8; 4]);
 
+impl ChunkType {
+    // The types in KNOWN_TYPES.
+    pub const IHDR: ChunkType = ChunkType::new_unchecked(*b"IHDR");
+    pub const PLTE: ChunkType = ChunkType::new_unchecked(*b"PLTE");
+    pub const IDAT: ChunkType = ChunkType::new_unchecked(*b"IDAT");
+    pub const IEND: ChunkType = ChunkType::new_unchecked(*b"IEND");
+    pub const ACTL: ChunkType = ChunkType::new_unchecked(*b"acTL");
+    pub const CHRM: ChunkType = ChunkType::new_unchecked(*b"cHRM");
+    pub const CICP: ChunkType = ChunkType::new_unchecked(*b"cICP");
+    pub const GAMA: ChunkType = ChunkType::new_unchecked(*b"gAMA");
+    pub const ICCP: ChunkType = ChunkType::new_unchecked(*b"iCCP");
+    pub const MDCV: ChunkType = ChunkType::new_unchecked(*b"mDCv");
+    pub const CLLI: ChunkType = ChunkType::new_unchecked(*b"cLLi");
+    pub const SBIT: ChunkType = ChunkType::new_unchecked(*b"sBIT");
+    pub const SRGB: ChunkType = ChunkType::new_unchecked(*b"sRGB");
+    pub const BKGD: ChunkType = ChunkType::new_unchecked(*b"bKGD");
+    pub const HIST: ChunkType = ChunkType::new_unchecked(*b"hIST");
+    pub const TRNS: ChunkType = ChunkType::new_unchecked(*b"tRNS");
+    pub const EXIF: ChunkType = ChunkType::new_unchecked(*b"eXIf");
+    pub const FCTL: ChunkType = ChunkType::new_unchecked(*b"fcTL");
+    pub const PHYS: ChunkType = ChunkType::new_unchecked(*b"pHYs");
+    pub const SPLT: ChunkType = ChunkType::new_unchecked(*b"sPLT");
+    pub const FDAT: ChunkType = ChunkType::new_unchecked(*b"fdAT");
+    pub const TIME: ChunkType = ChunkType::new_unchecked(*b"tIME");
+    pub const ITXT: ChunkType = ChunkType::new_unchecked(*b"iTXt");
+    pub const TEXT: ChunkType = ChunkType::new_unchecked(*b"tEXt");
+    pub const ZTXT: ChunkType = ChunkType::new_unchecked(*b"zTXt");
+    pub const OFFS: ChunkType = ChunkType::new_unchecked(*b"oFFs");
+    pub const PCAL: ChunkType = ChunkType::new_unchecked(*b"pCAL");
+    pub const SCAL: ChunkType = ChunkType::new_unchecked(*b"sCAL");
+    pub const GIFG: ChunkType = ChunkType::new_unchecked(*b"gIFg");
+    pub const GIFT: ChunkType = ChunkType::new_unchecked(*b"gIFt");
+    pub const GIFX: ChunkType = ChunkType::new_unchecked(*b"gIFx");
+    pub const STER: ChunkType = ChunkType::new_unchecked(*b"sTER");
+    pub const DSIG: ChunkType = ChunkType::new_unchecked(*b"dSIG");
+
+    /// Creates a type from any four bytes. Unlike [`ChunkType::try_from`] it doesn't check that
+    /// they are ASCII letters, which makes it usable in `const` contexts.
+    pub const fn new_unchecked(bytes: [u8; 4]) -> ChunkType {
+        ChunkType(bytes)
+    }
+}
+
 /// Four bits of the type code, namely bit 5 (value 32) of each byte, are used
 /// to convey chunk properties
 impl ChunkType {
@@ -13,6 +97,14 @@ impl ChunkType {
         self.0
     }
 
+    /// Returns the type code as a `&str`, or `None` if any of its bytes isn't an ASCII letter.
+    pub fn to_str(&self) -> Option<&str> {
+        match self.bytes_are_alphanumeric() {
+            true => std::str::from_utf8(&self.0).ok(),
+            false => None,
+        }
+    }
+
     pub fn bytes_are_alphanumeric(&self) -> bool {
         for byte in self.0 {
             if !ChunkType::is_valid_byte(byte) {
@@ -47,19 +139,38 @@ impl ChunkType {
         // Fourth byte holds the safe-to-copy bit.
         self.0[3] & ASCI_UPPER != 0
     }
+
+    /// Checks if the type is defined by the PNG spec or one of its registered extensions.
+    pub fn is_standard(&self) -> bool {
+        self.description().is_some()
+    }
+
+    /// Returns what a standard chunk type holds, e.g. "Image header" for `IHDR`, or `None` for
+    /// types the spec doesn't define.
+    pub fn description(&self) -> Option<&'static str> {
+        KNOWN_TYPES
+            .iter()
+            .find(|(chunk_type, _)| chunk_type == self)
+            .map(|(_, description)| *description)
+    }
 }
 
 impl ChunkType {
     pub fn is_valid_byte(byte: u8) -> bool {
-        (byte >= 65 && byte <= 90) || (byte >= 97 && byte <= 122)
+        (65..=90).contains(&byte) || (97..=122).contains(&byte)
     }
 }
 
+/// Fails unless all four bytes are ASCII letters.
 impl TryFrom<[u8; 4]> for ChunkType {
     type Error = Error;
 
     fn try_from(value: [u8; 4]) -> Result<Self> {
-        Ok(Self(value))
+        let chunk = Self(value);
+        match chunk.bytes_are_alphanumeric() {
+            true => Ok(chunk),
+            false => Err(PngError::InvalidChunkType(chunk.to_string())),
+        }
     }
 }
 
@@ -67,23 +178,36 @@ impl std::str::FromStr for ChunkType {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        let b = s.as_bytes();
-        let s: [u8; 4] = b.try_into()?;
-        let chunk = Self(s);
+        let b: [u8; 4] = s
+            .as_bytes()
+            .try_into()
+            .map_err(|_| PngError::InvalidChunkType(s.to_string()))?;
 
         // Note that we are only checking if the supplied bytes are in the valid ASCII range, not if the
         // reserved bit is actually valid. This is reflected in the tests.
-        match chunk.bytes_are_alphanumeric() {
-            true => Ok(chunk),
-            false => Err("Invalid type chunk code supplied".into()),
-        }
+        Self::try_from(b).map_err(|_| PngError::InvalidChunkType(s.to_string()))
     }
 }
 
+/// Printable ASCII bytes are written as-is, anything else is escaped as `\xNN` so that displaying
+/// a type code read from a damaged file never fails. A backslash is escaped too, so that the output
+/// can't be mistaken for an escape.
 impl std::fmt::Display for ChunkType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = std::str::from_utf8(&self.0).expect("Failed to convert to utf-8");
-        write!(f, "{}", s)
+        for byte in self.0 {
+            match byte.is_ascii_graphic() && byte != b'\\' {
+                true => write!(f, "{}", byte as char)?,
+                false => write!(f, "\\x{:02X}", byte)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serialized as its display string, e.g. `"ruSt"`.
+impl Serialize for ChunkType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
     }
 }
 
@@ -156,6 +280,39 @@ mod tests {
         assert!(!chunk.is_safe_to_copy());
     }
 
+    #[test]
+    pub fn chunk_type_is_standard() {
+        assert!(ChunkType::from_str("IHDR").unwrap().is_standard());
+        assert!(ChunkType::from_str("tEXt").unwrap().is_standard());
+        assert!(!ChunkType::from_str("ruSt").unwrap().is_standard());
+        assert!(!ChunkType::from_str("text").unwrap().is_standard());
+    }
+
+    #[test]
+    pub fn chunk_type_from_invalid_bytes() {
+        assert!(ChunkType::try_from([82, 117, 49, 116]).is_err());
+        assert!(ChunkType::try_from([0x8B, 117, 83, 116]).is_err());
+        assert_eq!(ChunkType::new_unchecked(*b"IEND"), ChunkType::IEND);
+    }
+
+    #[test]
+    pub fn chunk_type_description() {
+        assert_eq!(ChunkType::IHDR.description(), Some("Image header"));
+        assert_eq!(
+            ChunkType::from_str("zTXt").unwrap().description(),
+            Some("Compressed textual data")
+        );
+        assert_eq!(ChunkType::from_str("ruSt").unwrap().description(), None);
+    }
+
+    #[test]
+    pub fn known_types_are_valid() {
+        for (chunk_type, _) in KNOWN_TYPES {
+            assert!(chunk_type.is_valid(), "{}", chunk_type);
+            assert_eq!(ChunkType::try_from(chunk_type.bytes()).unwrap(), chunk_type);
+        }
+    }
+
     #[test]
     pub fn valid_chunk_is_valid() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -177,6 +334,27 @@ mod tests {
         assert_eq!(&chunk.to_string(), "RuSt");
     }
 
+    #[test]
+    pub fn chunk_type_string_escapes_invalid_bytes() {
+        let chunk = ChunkType::new_unchecked([0x8B, 117, 83, 116]);
+        assert_eq!(&chunk.to_string(), "\\x8BuSt");
+
+        let chunk = ChunkType::new_unchecked(*b"\\xAB");
+        assert_eq!(&chunk.to_string(), "\\x5CxAB");
+    }
+
+    #[test]
+    pub fn chunk_type_to_str() {
+        let chunk = ChunkType::from_str("RuSt").unwrap();
+        assert_eq!(chunk.to_str(), Some("RuSt"));
+
+        let chunk = ChunkType::new_unchecked([0x8B, 117, 83, 116]);
+        assert_eq!(chunk.to_str(), None);
+
+        let chunk = ChunkType::new_unchecked(*b"Ru1t");
+        assert_eq!(chunk.to_str(), None);
+    }
+
     #[test]
     pub fn chunk_type_trait_impls() {
         let chunk_type_1: ChunkType = TryFrom::try_from([82, 117, 83, 116]).unwrap();
@@ -0,0 +1,119 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+
+use crate::error::PngError;
+use crate::{Error, Result};
+
+/// Coding-independent code points (PNG third edition), identifying the colour space of the image
+/// by the code points defined in ITU-T H.273.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Cicp {
+    pub color_primaries: u8,
+    pub transfer_function: u8,
+    /// Always 0 for PNG since image data is stored as RGB.
+    pub matrix_coefficients: u8,
+    pub video_full_range: bool,
+}
+
+impl Cicp {
+    pub fn new(color_primaries: u8, transfer_function: u8, video_full_range: bool) -> Cicp {
+        Self {
+            color_primaries,
+            transfer_function,
+            matrix_coefficients: 0,
+            video_full_range,
+        }
+    }
+
+    pub fn to_chunk(self) -> Chunk {
+        let data = vec![
+            self.color_primaries,
+            self.transfer_function,
+            self.matrix_coefficients,
+            self.video_full_range as u8,
+        ];
+        Chunk::new(ChunkType::CICP, data)
+    }
+}
+
+impl TryFrom<&Chunk> for Cicp {
+    type Error = Error;
+
+    /// Fails if the chunk isn't a cICP chunk, isn't exactly 4 bytes long, or holds values the spec
+    /// doesn't allow in a PNG (non-zero matrix coefficients or a full-range flag other than 0 or 1).
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if *chunk.chunk_type() != ChunkType::CICP {
+            return Err(PngError::InvalidChunkData(format!(
+                "Expected a {} chunk, got {}",
+                ChunkType::CICP,
+                chunk.chunk_type()
+            )));
+        }
+
+        let data: [u8; 4] = chunk.data().try_into().map_err(|_| {
+            PngError::InvalidChunkData("cICP chunk must be exactly 4 bytes long".to_string())
+        })?;
+
+        if data[2] != 0 {
+            return Err(PngError::InvalidChunkData(
+                "cICP matrix coefficients must be 0 for RGB image data".to_string(),
+            ));
+        }
+
+        let video_full_range = match data[3] {
+            0 => false,
+            1 => true,
+            _ => {
+                return Err(PngError::InvalidChunkData(
+                    "cICP video full range flag must be 0 or 1".to_string(),
+                ))
+            }
+        };
+
+        Ok(Self {
+            color_primaries: data[0],
+            transfer_function: data[1],
+            matrix_coefficients: data[2],
+            video_full_range,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn cicp_chunk(data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::CICP, data)
+    }
+
+    #[test]
+    fn test_cicp_from_chunk() {
+        // BT.2100 primaries with the PQ transfer function, full range.
+        let cicp = Cicp::try_from(&cicp_chunk(vec![9, 16, 0, 1])).unwrap();
+        assert_eq!(cicp.color_primaries, 9);
+        assert_eq!(cicp.transfer_function, 16);
+        assert_eq!(cicp.matrix_coefficients, 0);
+        assert!(cicp.video_full_range);
+    }
+
+    #[test]
+    fn test_cicp_round_trip() {
+        let cicp = Cicp::new(1, 13, true);
+        let chunk = cicp.to_chunk();
+        assert_eq!(*chunk.chunk_type(), ChunkType::CICP);
+        assert_eq!(chunk.data(), &[1, 13, 0, 1]);
+        assert_eq!(Cicp::try_from(&chunk).unwrap(), cicp);
+    }
+
+    #[test]
+    fn test_cicp_invalid() {
+        assert!(Cicp::try_from(&cicp_chunk(vec![9, 16, 0])).is_err());
+        assert!(Cicp::try_from(&cicp_chunk(vec![9, 16, 1, 1])).is_err());
+        assert!(Cicp::try_from(&cicp_chunk(vec![9, 16, 0, 2])).is_err());
+
+        let chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), vec![9, 16, 0, 1]);
+        assert!(Cicp::try_from(&chunk).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use std::io;
+use std::str::Utf8Error;
+use std::string::FromUtf8Error;
+
+use pngme_rs::error::PngError;
+
+/// Everything that can make a command fail: an error from the library, or an outcome that only
+/// means something to the command line.
+#[derive(Debug)]
+pub enum CliError {
+    Png(PngError),
+    /// A glob pattern doesn't parse.
+    InvalidGlob(String),
+    /// The given number of files of a batch run failed.
+    BatchFailed(usize),
+    /// A file failed validation with the given number of issues.
+    ValidationFailed(usize),
+    /// Analysis found the given number of places that likely hold a payload.
+    HiddenDataFound(usize),
+}
+
+pub type Result<T> = std::result::Result<T, CliError>;
+
+impl CliError {
+    /// The process exit code. Finding hidden data gets its own, so that a CI pipeline can tell
+    /// it apart from a file that couldn't be read.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::HiddenDataFound(_) => 2,
+            _ => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Png(e) => write!(f, "{}", e),
+            CliError::InvalidGlob(reason) => write!(f, "Invalid glob pattern: {}", reason),
+            CliError::BatchFailed(count) => write!(f, "{} file(s) failed", count),
+            CliError::ValidationFailed(count) => write!(f, "Validation found {} issue(s)", count),
+            CliError::HiddenDataFound(count) => {
+                write!(f, "Found {} place(s) that likely hold hidden data", count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CliError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CliError::Png(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<PngError> for CliError {
+    fn from(e: PngError) -> Self {
+        CliError::Png(e)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(e: io::Error) -> Self {
+        CliError::Png(e.into())
+    }
+}
+
+impl From<Utf8Error> for CliError {
+    fn from(e: Utf8Error) -> Self {
+        CliError::Png(e.into())
+    }
+}
+
+impl From<FromUtf8Error> for CliError {
+    fn from(e: FromUtf8Error) -> Self {
+        CliError::Png(e.into())
+    }
+}
+
+impl From<glob::PatternError> for CliError {
+    fn from(e: glob::PatternError) -> Self {
+        CliError::InvalidGlob(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(CliError::HiddenDataFound(1).exit_code(), 2);
+        assert_eq!(CliError::Png(PngError::UnexpectedEof).exit_code(), 1);
+        assert_eq!(CliError::ValidationFailed(1).exit_code(), 1);
+    }
+}
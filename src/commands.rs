@@ -1 +1,1165 @@
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
 
+use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use serde::Serialize;
+
+use pngme_rs::analyze;
+use pngme_rs::armor;
+use pngme_rs::chunk::Chunk;
+use pngme_rs::chunk_type::ChunkType;
+use pngme_rs::compression::Compression;
+use pngme_rs::crypto;
+use pngme_rs::envelope;
+use pngme_rs::error::PngError;
+use pngme_rs::ihdr::Ihdr;
+use pngme_rs::payload;
+use pngme_rs::png::{Png, Position};
+use pngme_rs::stream::ParseOptions;
+use pngme_rs::text::{TextChunk, TextKind};
+use pngme_rs::validate;
+
+use crate::args::{
+    AnalyzeArgs, CheckArgs, Cli, Command, CompressionKind, DearmorArgs, DecodeArgs, EncodeArgs,
+    InfoArgs, OutputFormat, PrintArgs, RemoveArgs, ScanArgs, StripArgs, TextChunkKind,
+};
+use crate::batch::{self, FileResult};
+use crate::cli_error::{CliError, Result};
+
+pub fn run(cli: Cli) -> Result<()> {
+    let options = match cli.lenient {
+        true => ParseOptions::lenient(),
+        false => ParseOptions::default(),
+    };
+
+    match cli.command {
+        Command::Encode(args) => encode(args, options),
+        Command::Decode(args) => decode(args, options),
+        Command::Remove(args) => remove(args, options),
+        Command::Print(args) => print(args, options),
+        Command::Check(args) => check(args),
+        Command::Info(args) => info(args, options),
+        Command::Scan(args) => scan(args, options),
+        Command::Strip(args) => strip(args, options),
+        Command::Analyze(args) => analyze(args),
+        Command::Dearmor(args) => dearmor(args, options),
+    }
+}
+
+/// Passed in place of a path to read from stdin or write to stdout.
+const STDIO_PATH: &str = "-";
+
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new(STDIO_PATH)
+}
+
+fn read_bytes(path: &Path) -> Result<Vec<u8>> {
+    match is_stdio(path) {
+        true => {
+            let mut bytes = Vec::new();
+            io::stdin().lock().read_to_end(&mut bytes)?;
+            Ok(bytes)
+        }
+        false => Ok(fs::read(path)?),
+    }
+}
+
+fn write_bytes(path: &Path, bytes: &[u8]) -> Result<()> {
+    match is_stdio(path) {
+        true => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(bytes)?;
+            stdout.flush()?;
+        }
+        false => fs::write(path, bytes)?,
+    }
+    Ok(())
+}
+
+/// Reads the file according to `options`, printing any tolerated problems to stderr.
+fn read_png(path: &Path, options: ParseOptions) -> Result<Png> {
+    let (png, warnings) = match is_stdio(path) {
+        true => Png::read_from_with(io::stdin().lock(), options)?,
+        false => Png::read_from_with(BufReader::new(File::open(path)?), options)?,
+    };
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(png)
+}
+
+fn write_png(path: &Path, png: &Png) -> Result<()> {
+    match is_stdio(path) {
+        true => png.write_to(io::stdout().lock())?,
+        false => png.write_to(BufWriter::new(File::create(path)?))?,
+    }
+    Ok(())
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, value).map_err(io::Error::from)?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+pub fn encode(args: EncodeArgs, options: ParseOptions) -> Result<()> {
+    if let Some(input_file) = &args.input_file {
+        if is_stdio(input_file) && is_stdio(&args.file_path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Can't read both the PNG file and the input file from stdin",
+            )
+            .into());
+        }
+    }
+    let mut png = read_png(&args.file_path, options)?;
+
+    let chunks = match args.text_chunk {
+        Some(kind) => {
+            let kind = match kind {
+                TextChunkKind::Text => TextKind::Text,
+                TextChunkKind::Ztxt => TextKind::Compressed,
+                TextChunkKind::Itxt => TextKind::International { compressed: false },
+            };
+            let message = args.message.unwrap_or_default();
+            vec![TextChunk::new(kind, &args.chunk_type, &message).to_chunk()?]
+        }
+        None => {
+            let chunk_type = ChunkType::from_str(&args.chunk_type)?;
+            let payload = match (args.input_file, args.message) {
+                (Some(path), _) => read_bytes(&path)?,
+                (None, message) => message.unwrap_or_default().into_bytes(),
+            };
+            // Compress first, encrypted data doesn't compress.
+            let compression = args.compress.map(|kind| match kind {
+                CompressionKind::Deflate => Compression::Deflate,
+                CompressionKind::Zstd => Compression::Zstd,
+            });
+            let payload = match compression {
+                Some(compression) => compression.compress(&payload)?,
+                None => payload,
+            };
+            let payload = match &args.password {
+                Some(password) => crypto::encrypt(&payload, password)?,
+                None => payload,
+            };
+            // The signature covers the payload as stored, i.e. after encryption.
+            let signing_key = args.sign.as_deref().map(read_signing_key).transpose()?;
+            let payload = envelope::seal(
+                payload,
+                compression,
+                args.password.is_some(),
+                signing_key.as_ref(),
+            );
+            payload::split(&chunk_type, payload, args.max_chunk_size)?
+        }
+    };
+    png.insert_chunks(chunks, args.position)?;
+
+    let output = args.output.as_ref().unwrap_or(&args.file_path);
+    write_png(output, &png)
+}
+
+fn read_signing_key(path: &Path) -> Result<SigningKey> {
+    let pem = fs::read_to_string(path)?;
+    let key = SigningKey::from_pkcs8_pem(&pem).map_err(|e| {
+        PngError::Signing(format!("Can't read private key {}: {}", path.display(), e))
+    })?;
+    Ok(key)
+}
+
+fn read_verifying_key(path: &Path) -> Result<VerifyingKey> {
+    let pem = fs::read_to_string(path)?;
+    let key = VerifyingKey::from_public_key_pem(&pem).map_err(|e| {
+        PngError::Signing(format!("Can't read public key {}: {}", path.display(), e))
+    })?;
+    Ok(key)
+}
+
+/// Reassembles the payload stored in chunks of the given type, checks its signature against
+/// `verify` and, given a password, decrypts it. A plain payload stored in a single chunk is
+/// borrowed from the file.
+fn read_payload<'a>(
+    png: &'a Png,
+    chunk_type: &str,
+    password: Option<&str>,
+    verify: Option<&VerifyingKey>,
+) -> Result<Cow<'a, [u8]>> {
+    let chunks: Vec<&Chunk> = png.chunks_by_type(chunk_type).collect();
+    if chunks.is_empty() {
+        return Err(PngError::ChunkNotFound(chunk_type.to_string()).into());
+    }
+
+    Ok(payload::open(payload::join(chunks)?, password, verify)?)
+}
+
+/// Prints the errors of all failed files and a summary line. Fails if any file failed.
+fn finish_batch<T>(results: &[FileResult<T>], verb: &str) -> Result<()> {
+    let mut failed = 0;
+    for FileResult { path, result } in results {
+        if let Err(e) = result {
+            eprintln!("{}: Error: {}", path.display(), e);
+            failed += 1;
+        }
+    }
+    println!(
+        "{} {} of {} file(s), {} failed",
+        verb,
+        results.len() - failed,
+        results.len(),
+        failed
+    );
+
+    match failed {
+        0 => Ok(()),
+        _ => Err(CliError::BatchFailed(failed)),
+    }
+}
+
+pub fn decode(args: DecodeArgs, options: ParseOptions) -> Result<()> {
+    if args.glob {
+        return decode_batch(args, options);
+    }
+
+    let verify = args.verify.as_deref().map(read_verifying_key).transpose()?;
+    let png = read_png(&args.file_path, options)?;
+
+    if args.all {
+        let messages = read_all_messages(
+            &png,
+            &args.chunk_type,
+            args.password.as_deref(),
+            verify.as_ref(),
+        )?;
+        messages.iter().for_each(|message| println!("{}", message));
+        return Ok(());
+    }
+
+    let payload = read_payload(
+        &png,
+        &args.chunk_type,
+        args.password.as_deref(),
+        verify.as_ref(),
+    )?;
+
+    if args.armor {
+        let armored = payload::to_armor(&payload);
+        match &args.output_file {
+            Some(path) => write_bytes(path, armored.as_bytes())?,
+            None => print!("{}", armored),
+        }
+        return Ok(());
+    }
+
+    match &args.output_file {
+        Some(path) => write_bytes(path, &payload)?,
+        None => println!("{}", std::str::from_utf8(&payload)?),
+    }
+
+    Ok(())
+}
+
+/// Reads an armor block: writes out the bytes of a payload block, or inserts the chunk of a chunk
+/// block before `IEND` of the file given to `--into`.
+pub fn dearmor(args: DearmorArgs, options: ParseOptions) -> Result<()> {
+    let text = String::from_utf8(read_bytes(&args.armor_file)?)?;
+    let (label, bytes) = armor::dearmor(&text)?;
+
+    match label.as_str() {
+        armor::PAYLOAD_LABEL => {
+            let output = args.output.as_deref().unwrap_or(Path::new(STDIO_PATH));
+            write_bytes(output, &bytes)
+        }
+        armor::CHUNK_LABEL => {
+            let path = args.into.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "A chunk block needs --into to name the file to insert it into",
+                )
+            })?;
+            if is_stdio(&path) && is_stdio(&args.armor_file) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Can't read both the PNG file and the armor block from stdin",
+                )
+                .into());
+            }
+
+            let chunk = Chunk::try_from(bytes.as_ref())?;
+            let mut png = read_png(&path, options)?;
+            png.insert_chunk(chunk, Position::BeforeIend)?;
+            write_png(args.output.as_ref().unwrap_or(&path), &png)
+        }
+        label => Err(PngError::InvalidArmor(format!("Unknown label {}", label)).into()),
+    }
+}
+
+/// Decodes every message stored in chunks of the given type, see [`payload::join_all`]. Fails if
+/// there are none.
+fn read_all_messages(
+    png: &Png,
+    chunk_type: &str,
+    password: Option<&str>,
+    verify: Option<&VerifyingKey>,
+) -> Result<Vec<String>> {
+    let payloads = payload::join_all(png.chunks_by_type(chunk_type))?;
+    if payloads.is_empty() {
+        return Err(PngError::ChunkNotFound(chunk_type.to_string()).into());
+    }
+
+    payloads
+        .into_iter()
+        .map(|payload| {
+            let payload = payload::open(payload, password, verify)?;
+            Ok(String::from_utf8(payload.into_owned())?)
+        })
+        .collect()
+}
+
+/// Decodes the message of every file matching the pattern in the file path.
+fn decode_batch(args: DecodeArgs, options: ParseOptions) -> Result<()> {
+    let verify = args.verify.as_deref().map(read_verifying_key).transpose()?;
+    let results = batch::process(&args.file_path.to_string_lossy(), |path| {
+        let png = read_png(path, options)?;
+        let payload = read_payload(
+            &png,
+            &args.chunk_type,
+            args.password.as_deref(),
+            verify.as_ref(),
+        )?;
+        Ok(String::from_utf8(payload.into_owned())?)
+    })?;
+
+    for FileResult { path, result } in &results {
+        if let Ok(message) = result {
+            println!("{}: {}", path.display(), message);
+        }
+    }
+    finish_batch(&results, "Decoded")
+}
+
+pub fn remove(args: RemoveArgs, options: ParseOptions) -> Result<()> {
+    let mut png = read_png(&args.file_path, options)?;
+
+    let chunks = payload::remove(&mut png, &args.chunk_type)?;
+    write_png(&args.file_path, &png)?;
+    // Goes to stderr so it doesn't end up in the PNG when writing to stdout.
+    for chunk in chunks {
+        eprintln!(
+            "Removed chunk {} ({} bytes)",
+            chunk.chunk_type(),
+            chunk.length()
+        );
+    }
+
+    Ok(())
+}
+
+pub fn strip(args: StripArgs, options: ParseOptions) -> Result<()> {
+    let keep = args
+        .keep
+        .iter()
+        .map(|s| ChunkType::from_str(s))
+        .collect::<pngme_rs::Result<Vec<ChunkType>>>()?;
+    let mut png = read_png(&args.file_path, options)?;
+
+    let removed = png.strip_ancillary(&keep);
+    let output = args.output.as_ref().unwrap_or(&args.file_path);
+    write_png(output, &png)?;
+
+    // Goes to stderr so it doesn't end up in the PNG when writing to stdout.
+    for chunk in &removed {
+        eprintln!(
+            "Removed chunk {} ({} bytes)",
+            chunk.chunk_type(),
+            chunk.length()
+        );
+    }
+    // Each chunk also takes 12 bytes for its length, type code and crc.
+    let saved: u64 = removed.iter().map(|c| c.length() as u64 + 12).sum();
+    eprintln!("Removed {} chunk(s), saved {} bytes", removed.len(), saved);
+
+    Ok(())
+}
+
+pub fn print(args: PrintArgs, options: ParseOptions) -> Result<()> {
+    let png = read_png(&args.file_path, options)?;
+
+    let png = match args.ancillary_only || args.private_only || args.safe_to_copy {
+        true => {
+            let matches = |chunk_type: &ChunkType| {
+                (!args.ancillary_only || !chunk_type.is_critical())
+                    && (!args.private_only || !chunk_type.is_public())
+                    && (!args.safe_to_copy || chunk_type.is_safe_to_copy())
+            };
+            let chunks = png
+                .into_chunks()
+                .into_iter()
+                .filter(|c| matches(c.chunk_type()))
+                .collect();
+            Png::from_chunks(chunks)
+        }
+        false => png,
+    };
+
+    if args.text {
+        // A malformed chunk from a real-world file shouldn't hide all the others.
+        let texts: Vec<TextChunk> = png
+            .chunks()
+            .iter()
+            .filter(|c| TextChunk::is_text_chunk(c.chunk_type()))
+            .filter_map(|c| match TextChunk::try_from(c) {
+                Ok(text) => Some(text),
+                Err(e) => {
+                    eprintln!("Warning: Skipping {} chunk: {}", c.chunk_type(), e);
+                    None
+                }
+            })
+            .collect();
+
+        match args.format {
+            OutputFormat::Text => texts.iter().for_each(|text| println!("{}", text)),
+            OutputFormat::Json => print_json(&texts)?,
+        }
+        return Ok(());
+    }
+
+    match args.format {
+        OutputFormat::Text => print!("{}", png),
+        OutputFormat::Json => print_json(&png)?,
+    }
+
+    Ok(())
+}
+
+/// Prints all issues found in the file. With `--fix`, writes a repaired copy and reports the issues
+/// that remain in it instead. Fails if there are any issues left.
+pub fn check(args: CheckArgs) -> Result<()> {
+    let bytes = read_bytes(&args.file_path)?;
+    let report = validate::check(&bytes);
+
+    let mut remaining = None;
+    if let (true, Some(output)) = (args.fix, &args.output) {
+        let png = validate::repair(&bytes)?;
+        write_png(output, &png)?;
+        remaining = Some(validate::check(&png.as_bytes()));
+    }
+
+    let mut text = String::new();
+    match args.format {
+        OutputFormat::Text => {
+            text.push_str(&report.to_string());
+            if let (Some(remaining), Some(output)) = (&remaining, &args.output) {
+                text.push_str(&format!("Wrote repaired file to {}\n", output.display()));
+                if !remaining.is_valid() {
+                    text.push_str(&format!("Remaining issues:\n{}", remaining));
+                }
+            }
+        }
+        OutputFormat::Json => {
+            let output = CheckOutput {
+                issues: &report.issues,
+                output: remaining.as_ref().and(args.output.as_deref()),
+                remaining_issues: remaining.as_ref().map(|r| r.issues.as_slice()),
+            };
+            text = serde_json::to_string_pretty(&output).map_err(io::Error::from)? + "\n";
+        }
+    }
+    // Goes to stderr when the repaired file is written to stdout, so it doesn't end up in the PNG.
+    match remaining.is_some() && args.output.as_deref().is_some_and(is_stdio) {
+        true => eprint!("{}", text),
+        false => print!("{}", text),
+    }
+
+    let report = remaining.unwrap_or(report);
+    match report.is_valid() {
+        true => Ok(()),
+        false => Err(CliError::ValidationFailed(report.issues.len())),
+    }
+}
+
+/// Prints everything in the file that could hold hidden data. Fails if any of it has an entropy
+/// above the threshold, i.e. likely holds a payload.
+pub fn analyze(args: AnalyzeArgs) -> Result<()> {
+    let bytes = read_bytes(&args.file_path)?;
+    let analysis = analyze::analyze(&bytes)?;
+
+    match args.format {
+        OutputFormat::Text => print!("{}", analysis),
+        OutputFormat::Json => print_json(&analysis)?,
+    }
+
+    match analysis.likely_payloads(args.threshold).count() {
+        0 => Ok(()),
+        count => Err(CliError::HiddenDataFound(count)),
+    }
+}
+
+/// Lists the non-standard ancillary chunks of every PNG file below the directory.
+pub fn scan(args: ScanArgs, options: ParseOptions) -> Result<()> {
+    let results = batch::process(&batch::png_pattern(&args.dir), |path| {
+        let png = read_png(path, options)?;
+        let found: Vec<String> = png
+            .chunks()
+            .iter()
+            .filter(|c| !c.chunk_type().is_critical() && !c.chunk_type().is_standard())
+            .map(|c| format!("{} ({} bytes)", c.chunk_type(), c.length()))
+            .collect();
+        Ok(found)
+    })?;
+
+    for FileResult { path, result } in &results {
+        match result {
+            Ok(found) if !found.is_empty() => println!("{}: {}", path.display(), found.join(", ")),
+            _ => {}
+        }
+    }
+    finish_batch(&results, "Scanned")
+}
+
+/// The result of `pngme check --format json`. The repaired file and the issues left in it are
+/// only included with `--fix`.
+#[derive(Serialize)]
+struct CheckOutput<'a> {
+    issues: &'a [validate::Issue],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<&'a Path>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remaining_issues: Option<&'a [validate::Issue]>,
+}
+
+/// Image header plus chunk statistics, as printed by `pngme info`.
+#[derive(Serialize)]
+struct Info {
+    header: Ihdr,
+    chunks: usize,
+    critical_chunks: usize,
+    ancillary_chunks: usize,
+    ancillary_bytes: u64,
+}
+
+impl Info {
+    fn new(png: &Png) -> Result<Info> {
+        let (critical, ancillary): (Vec<&Chunk>, Vec<&Chunk>) = png
+            .chunks()
+            .iter()
+            .partition(|c| c.chunk_type().is_critical());
+
+        Ok(Self {
+            header: png.header()?,
+            chunks: png.chunks().len(),
+            critical_chunks: critical.len(),
+            ancillary_chunks: ancillary.len(),
+            ancillary_bytes: ancillary.iter().map(|c| c.length() as u64).sum(),
+        })
+    }
+}
+
+impl std::fmt::Display for Info {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.header)?;
+        writeln!(
+            f,
+            "Chunks: {} ({} critical, {} ancillary)",
+            self.chunks, self.critical_chunks, self.ancillary_chunks
+        )?;
+        writeln!(f, "Ancillary payload: {} bytes", self.ancillary_bytes)?;
+        Ok(())
+    }
+}
+
+pub fn info(args: InfoArgs, options: ParseOptions) -> Result<()> {
+    let png = read_png(&args.file_path, options)?;
+    let info = Info::new(&png)?;
+
+    match args.format {
+        OutputFormat::Text => print!("{}", info),
+        OutputFormat::Json => print_json(&info)?,
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pngme_rs::analyze::HIGH_ENTROPY;
+    use pngme_rs::envelope::Envelope;
+    use pngme_rs::payload::DEFAULT_MAX_CHUNK_SIZE;
+    use std::path::PathBuf;
+
+    // A 1x1 red RGB image.
+    const PNG_FILE: &[u8] = include_bytes!("../testdata/pixel.png");
+
+    fn temp_png(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("pngme-{}-{}.png", name, std::process::id()));
+        fs::write(&path, PNG_FILE).unwrap();
+        path
+    }
+
+    /// Arguments to encode `message` in a `ruSt` chunk of the file, with everything else left at
+    /// its default.
+    fn encode_args(path: &Path, message: &str) -> EncodeArgs {
+        EncodeArgs {
+            file_path: path.to_path_buf(),
+            chunk_type: "ruSt".to_string(),
+            message: Some(message.to_string()),
+            input_file: None,
+            max_chunk_size: DEFAULT_MAX_CHUNK_SIZE,
+            position: Position::BeforeIend,
+            text_chunk: None,
+            output: None,
+            password: None,
+            sign: None,
+            compress: None,
+        }
+    }
+
+    /// Arguments to decode the `ruSt` chunk of the file, with everything else left at its default.
+    fn decode_args(path: &Path) -> DecodeArgs {
+        DecodeArgs {
+            file_path: path.to_path_buf(),
+            chunk_type: "ruSt".to_string(),
+            password: None,
+            output_file: None,
+            glob: false,
+            verify: None,
+            all: false,
+            armor: false,
+        }
+    }
+
+    #[test]
+    fn test_encode_and_remove() {
+        let path = temp_png("encode");
+        encode(
+            encode_args(&path, "This is a secret message!"),
+            ParseOptions::default(),
+        )
+        .unwrap();
+
+        let png = read_png(&path, ParseOptions::default()).unwrap();
+        let chunk = png.chunk_by_type("ruSt").unwrap();
+        assert_eq!(chunk.data_as_string().unwrap(), "This is a secret message!");
+        assert_eq!(
+            png.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+
+        remove(
+            RemoveArgs {
+                file_path: path.clone(),
+                chunk_type: "ruSt".to_string(),
+            },
+            ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), PNG_FILE);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_to_output() {
+        let path = temp_png("output");
+        let output = path.with_extension("out.png");
+        encode(
+            EncodeArgs {
+                output: Some(output.clone()),
+                ..encode_args(&path, "This is a secret message!")
+            },
+            ParseOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), PNG_FILE);
+        assert!(read_png(&output, ParseOptions::default())
+            .unwrap()
+            .chunk_by_type("ruSt")
+            .is_some());
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_encode_with_password() {
+        let path = temp_png("password");
+        encode(
+            EncodeArgs {
+                password: Some("hunter2".to_string()),
+                ..encode_args(&path, "This is a secret message!")
+            },
+            ParseOptions::default(),
+        )
+        .unwrap();
+
+        let png = read_png(&path, ParseOptions::default()).unwrap();
+        let envelope = Envelope::open(png.chunk_by_type("ruSt").unwrap().data()).unwrap();
+        assert!(envelope.is_encrypted());
+        assert_eq!(
+            crypto::decrypt(envelope.body(), "hunter2").unwrap(),
+            b"This is a secret message!"
+        );
+
+        assert!(matches!(
+            decode(decode_args(&path), ParseOptions::default()),
+            Err(CliError::Png(PngError::PasswordRequired))
+        ));
+
+        let res = decode(
+            DecodeArgs {
+                password: Some("hunter3".to_string()),
+                ..decode_args(&path)
+            },
+            ParseOptions::default(),
+        );
+        assert!(matches!(
+            res,
+            Err(CliError::Png(PngError::DecryptionFailed))
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_encode_text_chunk() {
+        let path = temp_png("text");
+        encode(
+            EncodeArgs {
+                chunk_type: "Comment".to_string(),
+                text_chunk: Some(TextChunkKind::Ztxt),
+                ..encode_args(&path, "Café")
+            },
+            ParseOptions::default(),
+        )
+        .unwrap();
+
+        let png = read_png(&path, ParseOptions::default()).unwrap();
+        let text = TextChunk::try_from(png.chunk_by_type("zTXt").unwrap()).unwrap();
+        assert_eq!(text.keyword(), "Comment");
+        assert_eq!(text.text(), "Café");
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_embed_and_extract_file() {
+        let path = temp_png("file");
+        let input = path.with_extension("in.bin");
+        let output = path.with_extension("out.bin");
+        let payload: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        fs::write(&input, &payload).unwrap();
+
+        encode(
+            EncodeArgs {
+                message: None,
+                input_file: Some(input.clone()),
+                max_chunk_size: 100,
+                position: Position::AfterIhdr,
+                password: Some("hunter2".to_string()),
+                ..encode_args(&path, "")
+            },
+            ParseOptions::default(),
+        )
+        .unwrap();
+
+        let png = read_png(&path, ParseOptions::default()).unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        let chunks = types.iter().filter(|t| *t == "ruSt").count();
+        assert!(chunks > 1);
+        assert_eq!(types[0], "IHDR");
+        assert!(types[1..=chunks].iter().all(|t| t == "ruSt"));
+        assert_eq!(types[chunks + 1..], ["IDAT", "IEND"]);
+
+        decode(
+            DecodeArgs {
+                password: Some("hunter2".to_string()),
+                output_file: Some(output.clone()),
+                ..decode_args(&path)
+            },
+            ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(fs::read(&output).unwrap(), payload);
+
+        // Removing the payload takes all of its parts.
+        remove(
+            RemoveArgs {
+                file_path: path.clone(),
+                chunk_type: "ruSt".to_string(),
+            },
+            ParseOptions::default(),
+        )
+        .unwrap();
+        let png = read_png(&path, ParseOptions::default()).unwrap();
+        assert!(png.chunk_by_type("ruSt").is_none());
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(input).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_armor_and_dearmor() {
+        let path = temp_png("armor");
+        let armored = path.with_extension("asc");
+        let output = path.with_extension("out.bin");
+
+        encode(encode_args(&path, "message"), ParseOptions::default()).unwrap();
+        decode(
+            DecodeArgs {
+                output_file: Some(armored.clone()),
+                armor: true,
+                ..decode_args(&path)
+            },
+            ParseOptions::default(),
+        )
+        .unwrap();
+        assert!(fs::read_to_string(&armored)
+            .unwrap()
+            .starts_with("-----BEGIN PNGME PAYLOAD-----"));
+
+        let dearmor_args = |into: Option<&Path>| DearmorArgs {
+            armor_file: armored.clone(),
+            into: into.map(Path::to_path_buf),
+            output: Some(output.clone()),
+        };
+        dearmor(dearmor_args(None), ParseOptions::default()).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), b"message");
+
+        // A chunk block goes into the file given to `--into`, which it needs.
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"chunk".to_vec());
+        fs::write(&armored, chunk.to_armor()).unwrap();
+        assert!(dearmor(dearmor_args(None), ParseOptions::default()).is_err());
+        dearmor(dearmor_args(Some(&path)), ParseOptions::default()).unwrap();
+        let png = read_png(&output, ParseOptions::default()).unwrap();
+        let chunks: Vec<&Chunk> = png.chunks_by_type("ruSt").collect();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[1].data(), b"chunk");
+        assert_eq!(png.chunks().last().unwrap().chunk_type(), &ChunkType::IEND);
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(armored).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_print_text_skips_malformed_chunks() {
+        let path = temp_png("print-text");
+        let mut png = read_png(&path, ParseOptions::default()).unwrap();
+        png.append_chunk(Chunk::new(ChunkType::TEXT, b"no separator".to_vec()));
+        png.append_chunk(
+            TextChunk::new(TextKind::Text, "Comment", "Hi")
+                .to_chunk()
+                .unwrap(),
+        );
+        write_png(&path, &png).unwrap();
+
+        print(
+            PrintArgs {
+                file_path: path.clone(),
+                text: true,
+                ancillary_only: false,
+                private_only: false,
+                safe_to_copy: false,
+                format: OutputFormat::Text,
+            },
+            ParseOptions::default(),
+        )
+        .unwrap();
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_check_and_fix() {
+        let path = temp_png("check");
+        let output = path.with_extension("fixed.png");
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"garbage");
+        fs::write(&path, &bytes).unwrap();
+
+        let args = |fix| CheckArgs {
+            file_path: path.clone(),
+            fix,
+            output: fix.then(|| output.clone()),
+            format: OutputFormat::Text,
+        };
+        assert!(matches!(
+            check(args(false)),
+            Err(CliError::ValidationFailed(1))
+        ));
+        check(args(true)).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), PNG_FILE);
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_analyze() {
+        let path = temp_png("analyze");
+        let args = |threshold| AnalyzeArgs {
+            file_path: path.clone(),
+            threshold,
+            format: OutputFormat::Json,
+        };
+        analyze(args(HIGH_ENTROPY)).unwrap();
+
+        // Plain text is reported, but doesn't look like a payload.
+        encode(
+            encode_args(&path, "This is a secret message!"),
+            ParseOptions::default(),
+        )
+        .unwrap();
+        analyze(args(HIGH_ENTROPY)).unwrap();
+
+        // Even a short encrypted payload can't reach HIGH_ENTROPY, but is still caught.
+        encode(
+            EncodeArgs {
+                password: Some("hunter2".to_string()),
+                ..encode_args(&path, "short secret")
+            },
+            ParseOptions::default(),
+        )
+        .unwrap();
+        assert!(matches!(
+            analyze(args(HIGH_ENTROPY)),
+            Err(CliError::HiddenDataFound(1))
+        ));
+        assert!(matches!(
+            analyze(args(0.0)),
+            Err(CliError::HiddenDataFound(2))
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_info() {
+        let mut png = Png::try_from(PNG_FILE).unwrap();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"secret".to_vec(),
+        ));
+
+        let info = Info::new(&png).unwrap();
+        assert_eq!(info.header, png.header().unwrap());
+        assert_eq!(info.chunks, 4);
+        assert_eq!(info.critical_chunks, 3);
+        assert_eq!(info.ancillary_chunks, 1);
+        assert_eq!(info.ancillary_bytes, 6);
+        assert!(info
+            .to_string()
+            .ends_with("Chunks: 4 (3 critical, 1 ancillary)\nAncillary payload: 6 bytes\n"));
+    }
+
+    #[test]
+    fn test_decode_missing_chunk() {
+        let path = temp_png("decode");
+        let res = decode(decode_args(&path), ParseOptions::default());
+        assert!(res.is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_stdio_path() {
+        assert!(is_stdio(Path::new("-")));
+        assert!(!is_stdio(Path::new("./-")));
+        assert!(!is_stdio(Path::new("in.png")));
+    }
+
+    #[test]
+    fn test_encode_both_from_stdin() {
+        let res = encode(
+            EncodeArgs {
+                message: None,
+                input_file: Some(PathBuf::from("-")),
+                ..encode_args(&PathBuf::from("-"), "")
+            },
+            ParseOptions::default(),
+        );
+        assert!(matches!(res, Err(CliError::Png(PngError::Io(_)))));
+    }
+
+    #[test]
+    fn test_scan_and_decode_batch() {
+        let dir = std::env::temp_dir().join(format!("pngme-scan-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut png = Png::try_from(PNG_FILE).unwrap();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            b"secret".to_vec(),
+        ));
+        write_png(&dir.join("a.png"), &png).unwrap();
+        fs::write(dir.join("b.png"), PNG_FILE).unwrap();
+
+        scan(ScanArgs { dir: dir.clone() }, ParseOptions::default()).unwrap();
+
+        // b.png has no ruSt chunk, but a.png is still decoded.
+        let res = decode(
+            DecodeArgs {
+                glob: true,
+                ..decode_args(&dir.join("*.png"))
+            },
+            ParseOptions::default(),
+        );
+        assert!(matches!(res, Err(CliError::BatchFailed(1))));
+
+        fs::write(dir.join("c.png"), b"not a png").unwrap();
+        let res = scan(ScanArgs { dir: dir.clone() }, ParseOptions::default());
+        assert!(matches!(res, Err(CliError::BatchFailed(1))));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_strip() {
+        let path = temp_png("strip");
+        let output = path.with_extension("stripped.png");
+        let mut png = read_png(&path, ParseOptions::default()).unwrap();
+        for chunk_type in ["tEXt", "tIME", "ruSt"] {
+            png.append_chunk(Chunk::new(
+                ChunkType::from_str(chunk_type).unwrap(),
+                b"data".to_vec(),
+            ));
+        }
+        write_png(&path, &png).unwrap();
+
+        strip(
+            StripArgs {
+                file_path: path.clone(),
+                keep: vec!["tIME".to_string()],
+                output: Some(output.clone()),
+            },
+            ParseOptions::default(),
+        )
+        .unwrap();
+
+        let png = read_png(&output, ParseOptions::default()).unwrap();
+        assert_eq!(png.chunks().len(), 4);
+        assert!(png.chunk_by_type("tIME").is_some());
+        assert!(png.chunk_by_type("ruSt").is_none());
+        // The input file is left alone.
+        assert_eq!(
+            read_png(&path, ParseOptions::default())
+                .unwrap()
+                .chunks()
+                .len(),
+            6
+        );
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
+        use ed25519_dalek::pkcs8::{EncodePrivateKey, EncodePublicKey};
+
+        let path = temp_png("sign");
+        let key_file = path.with_extension("key.pem");
+        let pub_file = path.with_extension("pub.pem");
+        let other_pub_file = path.with_extension("other.pem");
+        let key = SigningKey::from_bytes(&[7; 32]);
+        fs::write(&key_file, key.to_pkcs8_pem(LineEnding::LF).unwrap()).unwrap();
+        let public_pem = key.verifying_key().to_public_key_pem(LineEnding::LF);
+        fs::write(&pub_file, public_pem.unwrap()).unwrap();
+        let other = SigningKey::from_bytes(&[8; 32]).verifying_key();
+        fs::write(
+            &other_pub_file,
+            other.to_public_key_pem(LineEnding::LF).unwrap(),
+        )
+        .unwrap();
+
+        encode(
+            EncodeArgs {
+                sign: Some(key_file.clone()),
+                ..encode_args(&path, "Signed message")
+            },
+            ParseOptions::default(),
+        )
+        .unwrap();
+
+        let png = read_png(&path, ParseOptions::default()).unwrap();
+        let verify = read_verifying_key(&pub_file).unwrap();
+        let payload = read_payload(&png, "ruSt", None, Some(&verify)).unwrap();
+        assert_eq!(*payload, *b"Signed message");
+        // Without --verify the signature is skipped but still stripped off.
+        let payload = read_payload(&png, "ruSt", None, None).unwrap();
+        assert_eq!(*payload, *b"Signed message");
+
+        let decode_with = |verify| {
+            decode(
+                DecodeArgs {
+                    verify: Some(verify),
+                    ..decode_args(&path)
+                },
+                ParseOptions::default(),
+            )
+        };
+        decode_with(pub_file.clone()).unwrap();
+        assert!(matches!(
+            decode_with(other_pub_file.clone()),
+            Err(CliError::Png(PngError::SignatureMismatch))
+        ));
+        assert!(matches!(
+            decode_with(key_file.clone()),
+            Err(CliError::Png(PngError::Signing(_)))
+        ));
+
+        for file in [path, key_file, pub_file, other_pub_file] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_decode_all() {
+        let path = temp_png("decode-all");
+        let long = "This message is split across several chunks.".repeat(10);
+        for message in ["first", &long, "last"] {
+            encode(
+                EncodeArgs {
+                    max_chunk_size: 100,
+                    ..encode_args(&path, message)
+                },
+                ParseOptions::default(),
+            )
+            .unwrap();
+        }
+
+        // Without --all, only the first of several plain chunks is read.
+        let png = read_png(&path, ParseOptions::default()).unwrap();
+        assert_eq!(*read_payload(&png, "ruSt", None, None).unwrap(), *b"first");
+        assert_eq!(
+            read_all_messages(&png, "ruSt", None, None).unwrap(),
+            vec!["first".to_string(), long, "last".to_string()]
+        );
+
+        let args = |chunk_type: &str| DecodeArgs {
+            chunk_type: chunk_type.to_string(),
+            all: true,
+            ..decode_args(&path)
+        };
+        decode(args("ruSt"), ParseOptions::default()).unwrap();
+        assert!(matches!(
+            decode(args("abCd"), ParseOptions::default()),
+            Err(CliError::Png(PngError::ChunkNotFound(_)))
+        ));
+
+        fs::remove_file(path).unwrap();
+    }
+}
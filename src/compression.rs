@@ -0,0 +1,158 @@
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+use crate::error::PngError;
+use crate::{Error, Result};
+
+/// Upper bound for decompressed data, so a small payload or text chunk from an untrusted file can't
+/// inflate into gigabytes.
+pub const MAX_DECOMPRESSED_LEN: u64 = 8 * 1024 * 1024;
+
+/// zstd level used when compressing, the library's default.
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 3;
+
+/// Algorithms a payload can be compressed with. The discriminant is the id stored in the payload
+/// envelope.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Compression {
+    /// zlib deflate, as used by the image data itself.
+    Deflate = 0,
+    /// Only available with the `zstd` feature, which is left out of the wasm build.
+    Zstd = 1,
+}
+
+impl Compression {
+    pub fn compress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Compression::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(zstd::encode_all(data, ZSTD_LEVEL)?),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => Err(PngError::UnsupportedCompression(self.to_string())),
+        }
+    }
+
+    /// Fails if `data` isn't a valid stream for the algorithm or inflates to more than
+    /// [`MAX_DECOMPRESSED_LEN`] bytes.
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>> {
+        // One byte more than allowed, to tell data right at the limit from data beyond it.
+        let limit = MAX_DECOMPRESSED_LEN + 1;
+        let mut decompressed = Vec::new();
+        let res = match self {
+            Compression::Deflate => ZlibDecoder::new(data)
+                .take(limit)
+                .read_to_end(&mut decompressed),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::Decoder::new(data)?
+                .take(limit)
+                .read_to_end(&mut decompressed),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => return Err(PngError::UnsupportedCompression(self.to_string())),
+        };
+        res.map_err(|e| {
+            PngError::InvalidChunkData(format!("Failed to decompress {} data: {}", self, e))
+        })?;
+        if decompressed.len() as u64 > MAX_DECOMPRESSED_LEN {
+            return Err(PngError::InvalidChunkData(format!(
+                "Decompressed data exceeds {} bytes",
+                MAX_DECOMPRESSED_LEN
+            )));
+        }
+        Ok(decompressed)
+    }
+}
+
+impl TryFrom<u8> for Compression {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Compression::Deflate),
+            1 => Ok(Compression::Zstd),
+            _ => Err(PngError::InvalidChunkData(format!(
+                "Unknown compression algorithm {}",
+                value
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compression::Deflate => write!(f, "deflate"),
+            Compression::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data = "This is where your secret message will be! ".repeat(50);
+        let algorithms = [
+            Compression::Deflate,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd,
+        ];
+        for compression in algorithms {
+            let compressed = compression.compress(data.as_bytes()).unwrap();
+            assert!(compressed.len() < data.len());
+            assert_eq!(
+                compression.decompress(&compressed).unwrap(),
+                data.as_bytes()
+            );
+            assert_eq!(
+                Compression::try_from(compression as u8).unwrap(),
+                compression
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        assert!(Compression::Deflate.decompress(b"not deflate").is_err());
+        assert!(Compression::Zstd.decompress(b"not zstd").is_err());
+        assert!(Compression::try_from(2).is_err());
+    }
+
+    #[test]
+    fn test_decompressed_len_limit() {
+        let algorithms = [
+            Compression::Deflate,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd,
+        ];
+        for compression in algorithms {
+            let data = vec![b'a'; MAX_DECOMPRESSED_LEN as usize];
+            let compressed = compression.compress(&data).unwrap();
+            assert_eq!(compression.decompress(&compressed).unwrap(), data);
+
+            let data = vec![b'a'; MAX_DECOMPRESSED_LEN as usize + 1];
+            let compressed = compression.compress(&data).unwrap();
+            assert!(matches!(
+                compression.decompress(&compressed),
+                Err(PngError::InvalidChunkData(_))
+            ));
+        }
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn test_zstd_disabled() {
+        assert!(matches!(
+            Compression::Zstd.compress(b"data"),
+            Err(PngError::UnsupportedCompression(_))
+        ));
+    }
+}
@@ -0,0 +1,102 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+
+use crate::error::PngError;
+use crate::Result;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Encrypts `plaintext` with AES-256-GCM under a key derived from `password` with Argon2.
+///
+/// The returned bytes are laid out as the 16-byte salt, the 12-byte nonce and the ciphertext with
+/// the authentication tag appended, so everything but the password needed to decrypt is stored
+/// alongside the data.
+pub fn encrypt(plaintext: &[u8], password: &str) -> Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let cipher = Aes256Gcm::new(&derive_key(password, &salt)?);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| PngError::Crypto("Failed to encrypt payload".to_string()))?;
+
+    Ok(salt
+        .iter()
+        .chain(nonce.iter())
+        .chain(ciphertext.iter())
+        .copied()
+        .collect())
+}
+
+/// Decrypts data produced by [`encrypt`]. Fails with [`PngError::DecryptionFailed`] if the
+/// password is wrong or the data has been tampered with.
+pub fn decrypt(data: &[u8], password: &str) -> Result<Vec<u8>> {
+    if data.len() < SALT_LEN + NONCE_LEN {
+        return Err(PngError::DecryptionFailed);
+    }
+    let (salt, rest) = data.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let cipher = Aes256Gcm::new(&derive_key(password, salt)?);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| PngError::DecryptionFailed)
+}
+
+fn derive_key(password: &str, salt: &[u8]) -> Result<Key<Aes256Gcm>> {
+    let mut key = Key::<Aes256Gcm>::default();
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| PngError::Crypto(e.to_string()))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &[u8] = b"This is where your secret message will be!";
+
+    #[test]
+    fn test_round_trip() {
+        let data = encrypt(MESSAGE, "hunter2").unwrap();
+        assert_eq!(data.len(), SALT_LEN + NONCE_LEN + MESSAGE.len() + 16);
+        assert_eq!(decrypt(&data, "hunter2").unwrap(), MESSAGE);
+    }
+
+    #[test]
+    fn test_salt_and_nonce_are_random() {
+        let a = encrypt(MESSAGE, "hunter2").unwrap();
+        let b = encrypt(MESSAGE, "hunter2").unwrap();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_wrong_password() {
+        let data = encrypt(MESSAGE, "hunter2").unwrap();
+        assert!(matches!(
+            decrypt(&data, "hunter3"),
+            Err(PngError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_data() {
+        let mut data = encrypt(MESSAGE, "hunter2").unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert!(matches!(
+            decrypt(&data, "hunter2"),
+            Err(PngError::DecryptionFailed)
+        ));
+
+        assert!(matches!(
+            decrypt(&data[..10], "hunter2"),
+            Err(PngError::DecryptionFailed)
+        ));
+    }
+}
@@ -0,0 +1,325 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey, SIGNATURE_LENGTH};
+
+use crate::compression::Compression;
+use crate::error::PngError;
+use crate::Result;
+
+/// Marks a payload as wrapped in an envelope.
+const MAGIC: [u8; 4] = *b"pmEV";
+
+/// The envelope version written by [`seal`]. Readers reject versions they don't know.
+pub const VERSION: u8 = 1;
+
+/// Magic, version and flags.
+const HEADER_LEN: usize = 6;
+
+/// The body is preceded by an Ed25519 signature over the rest of the header and the body.
+const FLAG_SIGNED: u8 = 0b0000_0001;
+/// The flags are followed by the id of the [`Compression`] the body was compressed with.
+const FLAG_COMPRESSED: u8 = 0b0000_0010;
+/// The body is encrypted with a password.
+const FLAG_ENCRYPTED: u8 = 0b0000_0100;
+
+/// A payload as stored in chunks, split into the optional compression algorithm and signature, and
+/// the body.
+///
+/// Payloads without an envelope are read as an unsigned, uncompressed body, so data written before
+/// envelopes existed still decodes.
+#[derive(Debug)]
+pub struct Envelope<'a> {
+    compression: Option<Compression>,
+    encrypted: bool,
+    signature: Option<Signature>,
+    /// Magic, version, flags and compression id, i.e. the header without the signature.
+    signed_header: &'a [u8],
+    body: &'a [u8],
+    header_len: usize,
+}
+
+impl<'a> Envelope<'a> {
+    /// Parses the envelope at the start of `data`. Fails on an unknown version, flags or
+    /// compression algorithm, or if the data is too short for the header it announces.
+    pub fn open(data: &'a [u8]) -> Result<Envelope<'a>> {
+        let Some(rest) = data.strip_prefix(&MAGIC) else {
+            return Ok(Self {
+                compression: None,
+                encrypted: false,
+                signature: None,
+                signed_header: &[],
+                body: data,
+                header_len: 0,
+            });
+        };
+
+        let [version, flags, rest @ ..] = rest else {
+            return Err(invalid("Truncated payload envelope"));
+        };
+        let mut rest = rest;
+        if *version != VERSION {
+            return Err(invalid(&format!(
+                "Unsupported payload envelope version {}",
+                version
+            )));
+        }
+        if flags & !(FLAG_SIGNED | FLAG_COMPRESSED | FLAG_ENCRYPTED) != 0 {
+            return Err(invalid(&format!(
+                "Unknown payload envelope flags {:#010b}",
+                flags
+            )));
+        }
+
+        let mut compression = None;
+        if flags & FLAG_COMPRESSED != 0 {
+            let (&id, tail) = rest
+                .split_first()
+                .ok_or_else(|| invalid("Truncated payload compression algorithm"))?;
+            compression = Some(Compression::try_from(id)?);
+            rest = tail;
+        }
+        let signed_header = &data[..data.len() - rest.len()];
+
+        let mut signature = None;
+        if flags & FLAG_SIGNED != 0 {
+            let (bytes, tail) = rest
+                .split_first_chunk::<SIGNATURE_LENGTH>()
+                .ok_or_else(|| invalid("Truncated payload signature"))?;
+            signature = Some(Signature::from_bytes(bytes));
+            rest = tail;
+        }
+
+        Ok(Self {
+            compression,
+            encrypted: flags & FLAG_ENCRYPTED != 0,
+            signature,
+            signed_header,
+            body: rest,
+            header_len: data.len() - rest.len(),
+        })
+    }
+
+    pub fn body(&self) -> &'a [u8] {
+        self.body
+    }
+
+    /// The algorithm the body was compressed with, if any. The body is stored compressed and, when
+    /// encrypted, is only decompressed after decrypting.
+    pub fn compression(&self) -> Option<Compression> {
+        self.compression
+    }
+
+    /// Checks if the body is encrypted. Payloads stored without an envelope report `false`.
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.signature.is_some()
+    }
+
+    /// Number of bytes in front of the body.
+    pub fn header_len(&self) -> usize {
+        self.header_len
+    }
+
+    /// Checks the signature against the header and the body, so neither the flags nor the
+    /// compression algorithm can be changed without notice. Fails with
+    /// [`PngError::SignatureMismatch`] if it doesn't match and with [`PngError::Signing`] if there
+    /// is no signature at all.
+    pub fn verify(&self, key: &VerifyingKey) -> Result<()> {
+        let signature = self
+            .signature
+            .ok_or_else(|| PngError::Signing("Payload is not signed".to_string()))?;
+        key.verify(&[self.signed_header, self.body].concat(), &signature)
+            .map_err(|_| PngError::SignatureMismatch)
+    }
+}
+
+/// Wraps `body` in an envelope recording the algorithm it was compressed with and whether it is
+/// encrypted, and signs the header and body with `key` if given. The body itself is stored
+/// unchanged.
+///
+/// Without compression, encryption or a key the body is returned as-is, unless it happens to start
+/// with the envelope magic, in which case it gets an empty envelope so [`Envelope::open`] can't
+/// misread it.
+pub fn seal(
+    body: Vec<u8>,
+    compression: Option<Compression>,
+    encrypted: bool,
+    key: Option<&SigningKey>,
+) -> Vec<u8> {
+    if compression.is_none() && !encrypted && key.is_none() && !body.starts_with(&MAGIC) {
+        return body;
+    }
+
+    let mut flags = 0;
+    if compression.is_some() {
+        flags |= FLAG_COMPRESSED;
+    }
+    if encrypted {
+        flags |= FLAG_ENCRYPTED;
+    }
+    if key.is_some() {
+        flags |= FLAG_SIGNED;
+    }
+
+    let mut data = Vec::with_capacity(HEADER_LEN + 1 + SIGNATURE_LENGTH + body.len());
+    data.extend_from_slice(&MAGIC);
+    data.push(VERSION);
+    data.push(flags);
+    if let Some(compression) = compression {
+        data.push(compression as u8);
+    }
+    if let Some(key) = key {
+        let signature = key.sign(&[data.as_slice(), &body].concat());
+        data.extend_from_slice(&signature.to_bytes());
+    }
+    data.extend_from_slice(&body);
+    data
+}
+
+fn invalid(reason: &str) -> PngError {
+    PngError::InvalidChunkData(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &[u8] = b"This is where your secret message will be!";
+
+    fn testing_key() -> SigningKey {
+        SigningKey::from_bytes(&[7; 32])
+    }
+
+    #[test]
+    fn test_unsigned_payload_is_stored_as_is() {
+        let data = seal(MESSAGE.to_vec(), None, false, None);
+        assert_eq!(data, MESSAGE);
+
+        let envelope = Envelope::open(&data).unwrap();
+        assert!(!envelope.is_signed());
+        assert_eq!(envelope.body(), MESSAGE);
+        assert_eq!(envelope.header_len(), 0);
+    }
+
+    #[test]
+    fn test_signed_round_trip() {
+        let key = testing_key();
+        let data = seal(MESSAGE.to_vec(), None, false, Some(&key));
+        assert_eq!(data.len(), HEADER_LEN + SIGNATURE_LENGTH + MESSAGE.len());
+
+        let envelope = Envelope::open(&data).unwrap();
+        assert!(envelope.is_signed());
+        assert_eq!(envelope.body(), MESSAGE);
+        assert_eq!(&data[envelope.header_len()..], MESSAGE);
+        envelope.verify(&key.verifying_key()).unwrap();
+    }
+
+    #[test]
+    fn test_tampered_or_wrong_key() {
+        let key = testing_key();
+        let mut data = seal(MESSAGE.to_vec(), None, false, Some(&key));
+        let other = SigningKey::from_bytes(&[8; 32]).verifying_key();
+        assert!(matches!(
+            Envelope::open(&data).unwrap().verify(&other),
+            Err(PngError::SignatureMismatch)
+        ));
+
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert!(matches!(
+            Envelope::open(&data).unwrap().verify(&key.verifying_key()),
+            Err(PngError::SignatureMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_header() {
+        let key = testing_key().verifying_key();
+        let data = seal(
+            MESSAGE.to_vec(),
+            Some(Compression::Deflate),
+            false,
+            Some(&testing_key()),
+        );
+        Envelope::open(&data).unwrap().verify(&key).unwrap();
+
+        let mut flipped = data.clone();
+        flipped[5] ^= FLAG_ENCRYPTED;
+        let envelope = Envelope::open(&flipped).unwrap();
+        assert!(envelope.is_encrypted());
+        assert!(matches!(
+            envelope.verify(&key),
+            Err(PngError::SignatureMismatch)
+        ));
+
+        let mut switched = data;
+        switched[HEADER_LEN] = Compression::Zstd as u8;
+        assert!(matches!(
+            Envelope::open(&switched).unwrap().verify(&key),
+            Err(PngError::SignatureMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_compressed_and_signed() {
+        let key = testing_key();
+        let data = seal(MESSAGE.to_vec(), Some(Compression::Zstd), false, Some(&key));
+        assert_eq!(
+            data.len(),
+            HEADER_LEN + 1 + SIGNATURE_LENGTH + MESSAGE.len()
+        );
+
+        let envelope = Envelope::open(&data).unwrap();
+        assert_eq!(envelope.compression(), Some(Compression::Zstd));
+        assert_eq!(envelope.body(), MESSAGE);
+        envelope.verify(&key.verifying_key()).unwrap();
+
+        let data = seal(MESSAGE.to_vec(), Some(Compression::Deflate), false, None);
+        let envelope = Envelope::open(&data).unwrap();
+        assert_eq!(envelope.compression(), Some(Compression::Deflate));
+        assert!(!envelope.is_signed());
+        assert_eq!(envelope.header_len(), HEADER_LEN + 1);
+    }
+
+    #[test]
+    fn test_encrypted_flag() {
+        let data = seal(MESSAGE.to_vec(), None, true, None);
+        let envelope = Envelope::open(&data).unwrap();
+        assert!(envelope.is_encrypted());
+        assert!(!envelope.is_signed());
+        assert_eq!(envelope.body(), MESSAGE);
+
+        assert!(!Envelope::open(MESSAGE).unwrap().is_encrypted());
+    }
+
+    #[test]
+    fn test_verify_unsigned() {
+        let envelope = Envelope::open(MESSAGE).unwrap();
+        assert!(matches!(
+            envelope.verify(&testing_key().verifying_key()),
+            Err(PngError::Signing(_))
+        ));
+    }
+
+    #[test]
+    fn test_payload_starting_with_magic() {
+        let payload = b"pmEV and then some".to_vec();
+        let data = seal(payload.clone(), None, false, None);
+        assert_ne!(data, payload);
+        let envelope = Envelope::open(&data).unwrap();
+        assert_eq!(envelope.body(), payload);
+        assert_eq!(envelope.header_len(), HEADER_LEN);
+    }
+
+    #[test]
+    fn test_invalid_envelopes() {
+        assert!(Envelope::open(b"pmEV").is_err());
+        assert!(Envelope::open(b"pmEV\x02\x00body").is_err());
+        assert!(Envelope::open(b"pmEV\x01\x08body").is_err());
+        assert!(Envelope::open(b"pmEV\x01\x80body").is_err());
+        assert!(Envelope::open(b"pmEV\x01\x02").is_err());
+        assert!(Envelope::open(b"pmEV\x01\x02\x09body").is_err());
+        assert!(Envelope::open(b"pmEV\x01\x01short signature").is_err());
+    }
+}
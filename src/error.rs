@@ -0,0 +1,164 @@
+use std::io;
+use std::str::Utf8Error;
+use std::string::FromUtf8Error;
+
+/// Everything that can go wrong when parsing, building or writing PNG data.
+#[derive(Debug)]
+pub enum PngError {
+    /// The input ended in the middle of a signature or chunk.
+    UnexpectedEof,
+    /// The input doesn't start with the 8-byte PNG signature.
+    InvalidSignature,
+    /// A type code that isn't four ASCII letters.
+    InvalidChunkType(String),
+    /// The length field of a chunk doesn't match the number of data bytes.
+    LengthMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// The crc stored in a chunk doesn't match the one computed from its type and data.
+    CrcMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// A chunk's length field exceeds the limit set in the parse options.
+    ChunkTooLong {
+        length: u32,
+        max: u32,
+    },
+    /// No chunk of the given type exists.
+    ChunkNotFound(String),
+    /// A chunk can't be inserted at the requested position.
+    InvalidPlacement(String),
+    /// The data of a chunk with a known layout is malformed.
+    InvalidChunkData(String),
+    /// An ASCII armor block is malformed or its checksum doesn't match.
+    InvalidArmor(String),
+    /// The payload uses a compression algorithm whose feature isn't enabled.
+    UnsupportedCompression(String),
+    /// The payload is encrypted but no password was given.
+    PasswordRequired,
+    /// The password is wrong or the encrypted data has been tampered with.
+    DecryptionFailed,
+    /// Key derivation or encryption failed.
+    Crypto(String),
+    /// A signing key can't be loaded or a payload that should be verified isn't signed.
+    Signing(String),
+    /// The payload signature doesn't match the payload or the public key.
+    SignatureMismatch,
+    Utf8(Utf8Error),
+    Io(io::Error),
+}
+
+impl std::fmt::Display for PngError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PngError::UnexpectedEof => write!(f, "Unexpected end of data"),
+            PngError::InvalidSignature => write!(f, "Invalid PNG signature"),
+            PngError::InvalidChunkType(code) => write!(f, "Invalid chunk type code \"{}\"", code),
+            PngError::LengthMismatch { expected, actual } => write!(
+                f,
+                "Chunk length is {} but {} bytes of data were found",
+                expected, actual
+            ),
+            PngError::CrcMismatch { expected, actual } => write!(
+                f,
+                "Chunk crc is {} but the data has a crc of {}",
+                expected, actual
+            ),
+            PngError::ChunkTooLong { length, max } => write!(
+                f,
+                "Chunk length {} exceeds the maximum of {} bytes",
+                length, max
+            ),
+            PngError::ChunkNotFound(chunk_type) => {
+                write!(f, "No chunk of type {} found", chunk_type)
+            }
+            PngError::InvalidPlacement(reason) => write!(f, "Invalid placement: {}", reason),
+            PngError::InvalidChunkData(reason) => write!(f, "Invalid chunk data: {}", reason),
+            PngError::InvalidArmor(reason) => write!(f, "Invalid armor: {}", reason),
+            PngError::UnsupportedCompression(algorithm) => write!(
+                f,
+                "{} compression isn't supported by this build of pngme",
+                algorithm
+            ),
+            PngError::PasswordRequired => {
+                write!(f, "Payload is encrypted, a password is needed to decode it")
+            }
+            PngError::DecryptionFailed => {
+                write!(f, "Failed to decrypt: wrong password or tampered data")
+            }
+            PngError::Crypto(reason) => write!(f, "Encryption error: {}", reason),
+            PngError::Signing(reason) => write!(f, "Signing error: {}", reason),
+            PngError::SignatureMismatch => write!(
+                f,
+                "Signature verification failed: the payload was tampered with or signed with a \
+                 different key"
+            ),
+            PngError::Utf8(e) => write!(f, "Data is not valid UTF-8: {}", e),
+            PngError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for PngError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PngError::Utf8(e) => Some(e),
+            PngError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Running out of input surfaces as [`PngError::UnexpectedEof`] no matter whether the bytes came
+/// from a slice or a reader.
+impl From<io::Error> for PngError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => PngError::UnexpectedEof,
+            _ => PngError::Io(e),
+        }
+    }
+}
+
+impl From<Utf8Error> for PngError {
+    fn from(e: Utf8Error) -> Self {
+        PngError::Utf8(e)
+    }
+}
+
+impl From<FromUtf8Error> for PngError {
+    fn from(e: FromUtf8Error) -> Self {
+        PngError::Utf8(e.utf8_error())
+    }
+}
+
+impl From<base64::DecodeError> for PngError {
+    fn from(e: base64::DecodeError) -> Self {
+        PngError::InvalidArmor(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_eof_maps_to_unexpected_eof() {
+        let e = PngError::from(io::Error::from(io::ErrorKind::UnexpectedEof));
+        assert!(matches!(e, PngError::UnexpectedEof));
+
+        let e = PngError::from(io::Error::from(io::ErrorKind::NotFound));
+        assert!(matches!(e, PngError::Io(_)));
+    }
+
+    #[test]
+    fn test_error_display() {
+        let e = PngError::CrcMismatch {
+            expected: 1,
+            actual: 2,
+        };
+        assert_eq!(e.to_string(), "Chunk crc is 1 but the data has a crc of 2");
+    }
+}
@@ -0,0 +1,247 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+
+use crate::error::PngError;
+use crate::{Error, Result};
+
+/// Chromaticity coordinates are stored in units of 0.00002, so 50000 corresponds to 1.0.
+const MAX_CHROMATICITY: u16 = 50000;
+
+/// A CIE 1931 xy chromaticity, each coordinate in units of 0.00002.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Chromaticity {
+    pub x: u16,
+    pub y: u16,
+}
+
+impl Chromaticity {
+    fn is_valid(&self) -> bool {
+        self.x <= MAX_CHROMATICITY && self.y <= MAX_CHROMATICITY
+    }
+}
+
+/// Mastering display colour volume (SMPTE ST 2086), describing the display an HDR image was
+/// graded on.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Mdcv {
+    /// Red, green and blue primaries, in that order.
+    pub primaries: [Chromaticity; 3],
+    pub white_point: Chromaticity,
+    /// Maximum luminance in units of 0.0001 cd/m².
+    pub max_luminance: u32,
+    /// Minimum luminance in units of 0.0001 cd/m².
+    pub min_luminance: u32,
+}
+
+impl Mdcv {
+    pub fn new(
+        primaries: [Chromaticity; 3],
+        white_point: Chromaticity,
+        max_luminance: u32,
+        min_luminance: u32,
+    ) -> Result<Mdcv> {
+        let mdcv = Self {
+            primaries,
+            white_point,
+            max_luminance,
+            min_luminance,
+        };
+        mdcv.validate()?;
+        Ok(mdcv)
+    }
+
+    /// Checks that all chromaticities are in the range 0.0 to 1.0 and that the minimum luminance
+    /// is below the maximum luminance.
+    pub fn validate(&self) -> Result<()> {
+        if !self.primaries.iter().all(Chromaticity::is_valid) || !self.white_point.is_valid() {
+            return Err(PngError::InvalidChunkData(
+                "mDCv chromaticity out of range".to_string(),
+            ));
+        }
+
+        if self.min_luminance >= self.max_luminance {
+            return Err(PngError::InvalidChunkData(
+                "mDCv minimum luminance must be below the maximum luminance".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn to_chunk(self) -> Chunk {
+        let data: Vec<u8> = self
+            .primaries
+            .iter()
+            .chain(std::iter::once(&self.white_point))
+            .flat_map(|c| c.x.to_be_bytes().into_iter().chain(c.y.to_be_bytes()))
+            .chain(self.max_luminance.to_be_bytes())
+            .chain(self.min_luminance.to_be_bytes())
+            .collect();
+        Chunk::new(ChunkType::MDCV, data)
+    }
+}
+
+impl TryFrom<&Chunk> for Mdcv {
+    type Error = Error;
+
+    /// Fails if the chunk isn't an mDCv chunk, isn't exactly 24 bytes long or holds values out of
+    /// range.
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if *chunk.chunk_type() != ChunkType::MDCV {
+            return Err(PngError::InvalidChunkData(format!(
+                "Expected a {} chunk, got {}",
+                ChunkType::MDCV,
+                chunk.chunk_type()
+            )));
+        }
+
+        let data: [u8; 24] = chunk.data().try_into().map_err(|_| {
+            PngError::InvalidChunkData("mDCv chunk must be exactly 24 bytes long".to_string())
+        })?;
+
+        let u16_at = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+        let u32_at =
+            |i: usize| u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let chromaticity_at = |i: usize| Chromaticity {
+            x: u16_at(i),
+            y: u16_at(i + 2),
+        };
+
+        Mdcv::new(
+            [chromaticity_at(0), chromaticity_at(4), chromaticity_at(8)],
+            chromaticity_at(12),
+            u32_at(16),
+            u32_at(20),
+        )
+    }
+}
+
+/// Content light level information (CTA-861.3) of an HDR image. A value of 0 means unknown.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Clli {
+    /// Maximum content light level in units of 0.0001 cd/m².
+    pub max_cll: u32,
+    /// Maximum frame-average light level in units of 0.0001 cd/m².
+    pub max_fall: u32,
+}
+
+impl Clli {
+    pub fn new(max_cll: u32, max_fall: u32) -> Result<Clli> {
+        let clli = Self { max_cll, max_fall };
+        clli.validate()?;
+        Ok(clli)
+    }
+
+    /// Checks that the frame-average level doesn't exceed the maximum level, unless either is
+    /// unknown.
+    pub fn validate(&self) -> Result<()> {
+        if self.max_cll != 0 && self.max_fall > self.max_cll {
+            return Err(PngError::InvalidChunkData(
+                "cLLi MaxFALL must not exceed MaxCLL".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn to_chunk(self) -> Chunk {
+        let data: Vec<u8> = self
+            .max_cll
+            .to_be_bytes()
+            .into_iter()
+            .chain(self.max_fall.to_be_bytes())
+            .collect();
+        Chunk::new(ChunkType::CLLI, data)
+    }
+}
+
+impl TryFrom<&Chunk> for Clli {
+    type Error = Error;
+
+    /// Fails if the chunk isn't a cLLi chunk, isn't exactly 8 bytes long or holds values out of
+    /// range.
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if *chunk.chunk_type() != ChunkType::CLLI {
+            return Err(PngError::InvalidChunkData(format!(
+                "Expected a {} chunk, got {}",
+                ChunkType::CLLI,
+                chunk.chunk_type()
+            )));
+        }
+
+        let data: [u8; 8] = chunk.data().try_into().map_err(|_| {
+            PngError::InvalidChunkData("cLLi chunk must be exactly 8 bytes long".to_string())
+        })?;
+
+        Clli::new(
+            u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn bt2020_mdcv() -> Mdcv {
+        Mdcv::new(
+            [
+                Chromaticity { x: 35400, y: 14600 },
+                Chromaticity { x: 8500, y: 39850 },
+                Chromaticity { x: 6550, y: 2300 },
+            ],
+            Chromaticity { x: 15635, y: 16450 },
+            10_000_000,
+            50,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_mdcv_round_trip() {
+        let mdcv = bt2020_mdcv();
+        let chunk = mdcv.to_chunk();
+        assert_eq!(*chunk.chunk_type(), ChunkType::MDCV);
+        assert_eq!(chunk.data().len(), 24);
+        assert_eq!(&chunk.data()[..4], &[0x8a, 0x48, 0x39, 0x08]);
+        assert_eq!(Mdcv::try_from(&chunk).unwrap(), mdcv);
+    }
+
+    #[test]
+    fn test_mdcv_invalid() {
+        let mut mdcv = bt2020_mdcv();
+        mdcv.white_point.x = 50001;
+        assert!(mdcv.validate().is_err());
+        assert!(Mdcv::try_from(&mdcv.to_chunk()).is_err());
+
+        let mut mdcv = bt2020_mdcv();
+        mdcv.min_luminance = mdcv.max_luminance;
+        assert!(mdcv.validate().is_err());
+
+        let chunk = Chunk::new(ChunkType::MDCV, vec![0; 23]);
+        assert!(Mdcv::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_clli_round_trip() {
+        let clli = Clli::new(10_000_000, 4_000_000).unwrap();
+        let chunk = clli.to_chunk();
+        assert_eq!(*chunk.chunk_type(), ChunkType::CLLI);
+        assert_eq!(
+            chunk.data(),
+            &[0x00, 0x98, 0x96, 0x80, 0x00, 0x3d, 0x09, 0x00]
+        );
+        assert_eq!(Clli::try_from(&chunk).unwrap(), clli);
+    }
+
+    #[test]
+    fn test_clli_invalid() {
+        assert!(Clli::new(4_000_000, 10_000_000).is_err());
+        assert!(Clli::new(0, 10_000_000).is_ok());
+
+        let chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), vec![0; 8]);
+        assert!(Clli::try_from(&chunk).is_err());
+    }
+}
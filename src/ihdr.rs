@@ -0,0 +1,247 @@
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+
+use crate::error::PngError;
+use crate::{Error, Result};
+
+/// Width and height must fit into 31 bits.
+const MAX_DIMENSION: u32 = (1 << 31) - 1;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorType {
+    Grayscale = 0,
+    Rgb = 2,
+    Indexed = 3,
+    GrayscaleAlpha = 4,
+    Rgba = 6,
+}
+
+impl ColorType {
+    /// Returns the bit depths the spec allows for this color type.
+    pub fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(PngError::InvalidChunkData(format!(
+                "Unknown color type {}",
+                value
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for ColorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "Grayscale",
+            ColorType::Rgb => "RGB",
+            ColorType::Indexed => "Indexed",
+            ColorType::GrayscaleAlpha => "Grayscale + alpha",
+            ColorType::Rgba => "RGBA",
+        };
+        write!(f, "{} ({})", name, *self as u8)
+    }
+}
+
+/// The image header, always the first chunk of a PNG file.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    /// 0 for no interlacing, 1 for Adam7.
+    pub interlace_method: u8,
+}
+
+impl Ihdr {
+    /// Checks dimensions, the bit depth for the color type and the method fields against the
+    /// values the spec allows.
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=MAX_DIMENSION).contains(&self.width) || !(1..=MAX_DIMENSION).contains(&self.height)
+        {
+            return Err(PngError::InvalidChunkData(format!(
+                "Image dimensions {}x{} out of range",
+                self.width, self.height
+            )));
+        }
+
+        if !self
+            .color_type
+            .allowed_bit_depths()
+            .contains(&self.bit_depth)
+        {
+            return Err(PngError::InvalidChunkData(format!(
+                "Bit depth {} is not allowed for color type {}",
+                self.bit_depth, self.color_type
+            )));
+        }
+
+        if self.compression_method != 0 || self.filter_method != 0 || self.interlace_method > 1 {
+            return Err(PngError::InvalidChunkData(
+                "Unknown compression, filter or interlace method".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn to_chunk(self) -> Chunk {
+        let data: Vec<u8> = self
+            .width
+            .to_be_bytes()
+            .into_iter()
+            .chain(self.height.to_be_bytes())
+            .chain([
+                self.bit_depth,
+                self.color_type as u8,
+                self.compression_method,
+                self.filter_method,
+                self.interlace_method,
+            ])
+            .collect();
+        Chunk::new(ChunkType::IHDR, data)
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = Error;
+
+    /// Fails if the chunk isn't an IHDR chunk, isn't exactly 13 bytes long or holds values the spec
+    /// doesn't allow.
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if *chunk.chunk_type() != ChunkType::IHDR {
+            return Err(PngError::InvalidChunkData(format!(
+                "Expected a {} chunk, got {}",
+                ChunkType::IHDR,
+                chunk.chunk_type()
+            )));
+        }
+
+        let data: [u8; 13] = chunk.data().try_into().map_err(|_| {
+            PngError::InvalidChunkData("IHDR chunk must be exactly 13 bytes long".to_string())
+        })?;
+
+        let ihdr = Self {
+            width: u32::from_be_bytes(Chunk::read_4_bytes(&data, 0)?),
+            height: u32::from_be_bytes(Chunk::read_4_bytes(&data, 4)?),
+            bit_depth: data[8],
+            color_type: ColorType::try_from(data[9])?,
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method: data[12],
+        };
+        ihdr.validate()?;
+        Ok(ihdr)
+    }
+}
+
+impl std::fmt::Display for Ihdr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Width: {}", self.width)?;
+        writeln!(f, "Height: {}", self.height)?;
+        writeln!(f, "Bit depth: {}", self.bit_depth)?;
+        writeln!(f, "Color type: {}", self.color_type)?;
+        writeln!(f, "Compression method: {}", self.compression_method)?;
+        writeln!(f, "Filter method: {}", self.filter_method)?;
+        let interlace = match self.interlace_method {
+            1 => "Adam7",
+            _ => "None",
+        };
+        writeln!(
+            f,
+            "Interlace method: {} ({})",
+            interlace, self.interlace_method
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::PNG_FILE;
+    use crate::png::Png;
+
+    fn testing_ihdr() -> Ihdr {
+        Ihdr {
+            width: 640,
+            height: 480,
+            bit_depth: 8,
+            color_type: ColorType::Rgba,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 1,
+        }
+    }
+
+    #[test]
+    fn test_ihdr_from_png() {
+        let png = Png::try_from(PNG_FILE.as_ref()).unwrap();
+        let ihdr = Ihdr::try_from(&png.chunks()[0]).unwrap();
+        assert_eq!(ihdr.width, 1);
+        assert_eq!(ihdr.height, 1);
+        assert_eq!(ihdr.bit_depth, 8);
+        assert_eq!(ihdr.color_type, ColorType::Rgb);
+        assert_eq!(ihdr.interlace_method, 0);
+    }
+
+    #[test]
+    fn test_ihdr_round_trip() {
+        let ihdr = testing_ihdr();
+        let chunk = ihdr.to_chunk();
+        assert_eq!(chunk.length(), 13);
+        assert_eq!(Ihdr::try_from(&chunk).unwrap(), ihdr);
+    }
+
+    #[test]
+    fn test_invalid_ihdr() {
+        let mut ihdr = testing_ihdr();
+        ihdr.width = 0;
+        assert!(Ihdr::try_from(&ihdr.to_chunk()).is_err());
+
+        let mut ihdr = testing_ihdr();
+        ihdr.bit_depth = 4;
+        assert!(Ihdr::try_from(&ihdr.to_chunk()).is_err());
+
+        let mut ihdr = testing_ihdr();
+        ihdr.interlace_method = 2;
+        assert!(Ihdr::try_from(&ihdr.to_chunk()).is_err());
+
+        let mut data = testing_ihdr().to_chunk().data().to_vec();
+        data[9] = 5;
+        let chunk = Chunk::new(ChunkType::IHDR, data);
+        assert!(Ihdr::try_from(&chunk).is_err());
+
+        let chunk = Chunk::new(ChunkType::IHDR, vec![0; 12]);
+        assert!(Ihdr::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_ihdr_display() {
+        let s = testing_ihdr().to_string();
+        assert!(s.contains("Width: 640\n"));
+        assert!(s.contains("Color type: RGBA (6)\n"));
+        assert!(s.contains("Interlace method: Adam7 (1)\n"));
+    }
+}
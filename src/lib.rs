@@ -0,0 +1,27 @@
+//! Reading, writing and editing PNG chunks, and hiding messages in them.
+//!
+//! Everything here works on bytes in memory or generic readers and writers, so the library builds
+//! for targets without a filesystem. With the `wasm` feature it exposes the [`wasm`] bindings.
+
+pub mod analyze;
+pub mod armor;
+pub mod chunk;
+pub mod chunk_ref;
+pub mod chunk_type;
+pub mod cicp;
+pub mod compression;
+pub mod crypto;
+pub mod envelope;
+pub mod error;
+pub mod hdr;
+pub mod ihdr;
+pub mod payload;
+pub mod png;
+pub mod stream;
+pub mod text;
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub type Error = error::PngError;
+pub type Result<T> = std::result::Result<T, Error>;
@@ -1,12 +1,15 @@
+use clap::Parser;
+
 mod args;
-mod chunk;
-mod chunk_type;
+mod batch;
+mod cli_error;
 mod commands;
-mod png;
-
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
 
 fn main() {
-    todo!()
+    let cli = args::Cli::parse();
+
+    if let Err(e) = commands::run(cli) {
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
+    }
 }
@@ -0,0 +1,382 @@
+use std::borrow::Cow;
+
+use ed25519_dalek::VerifyingKey;
+
+use crate::armor;
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::crypto;
+use crate::envelope::Envelope;
+use crate::png::Png;
+
+use crate::error::PngError;
+use crate::Result;
+
+/// Default upper bound for the data length of a single payload chunk.
+pub const DEFAULT_MAX_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Marks the data of a chunk as one part of a payload split across several chunks.
+const SEQUENCE_MAGIC: [u8; 4] = *b"pmSQ";
+
+/// Magic, part index and part count, the latter two as big endian u32.
+const SEQUENCE_HEADER_LEN: usize = 12;
+
+/// Stores `payload` in chunks of the given type.
+///
+/// A payload that fits into `max_chunk_size` bytes is moved as-is into a single chunk. Larger
+/// payloads are split into parts, each prefixed with a sequence header holding its index and the
+/// total number of parts, so that [`join`] can put them back together in order.
+pub fn split(
+    chunk_type: &ChunkType,
+    payload: Vec<u8>,
+    max_chunk_size: usize,
+) -> Result<Vec<Chunk>> {
+    let new_chunk = |data| Ok(Chunk::new(*chunk_type, data));
+
+    // A payload that happens to start with the magic is always sequenced so join can't misread it.
+    if payload.len() <= max_chunk_size && !payload.starts_with(&SEQUENCE_MAGIC) {
+        return Ok(vec![new_chunk(payload)?]);
+    }
+
+    if max_chunk_size <= SEQUENCE_HEADER_LEN {
+        return Err(PngError::InvalidChunkData(format!(
+            "Max chunk size must be larger than {} bytes to split a payload",
+            SEQUENCE_HEADER_LEN
+        )));
+    }
+
+    let parts = payload.chunks(max_chunk_size - SEQUENCE_HEADER_LEN);
+    let count = parts.len() as u32;
+    parts
+        .enumerate()
+        .map(|(index, part)| {
+            let data = SEQUENCE_MAGIC
+                .iter()
+                .copied()
+                .chain((index as u32).to_be_bytes())
+                .chain(count.to_be_bytes())
+                .chain(part.iter().copied())
+                .collect();
+            new_chunk(data)
+        })
+        .collect()
+}
+
+/// Reassembles a payload stored by [`split`].
+///
+/// If the first chunk isn't part of a sequence, its data is borrowed as-is. Otherwise every chunk
+/// must carry a sequence header, and all parts must be present exactly once, in any order.
+pub fn join<'a>(chunks: impl IntoIterator<Item = &'a Chunk>) -> Result<Cow<'a, [u8]>> {
+    let chunks: Vec<&Chunk> = chunks.into_iter().collect();
+
+    let first = chunks.first().ok_or_else(|| {
+        PngError::InvalidChunkData("No chunks to read the payload from".to_string())
+    })?;
+    if !is_sequenced(first) {
+        return Ok(Cow::Borrowed(first.data()));
+    }
+
+    let mut parts: Vec<Option<&[u8]>> = vec![None; chunks.len()];
+    for chunk in chunks {
+        let (index, count, part) = read_sequence_header(chunk)?;
+
+        if count as usize != parts.len() || index >= count {
+            return Err(PngError::InvalidChunkData(format!(
+                "Payload part {} of {} doesn't fit a sequence of {} parts",
+                index,
+                count,
+                parts.len()
+            )));
+        }
+        if parts[index as usize].replace(part).is_some() {
+            return Err(PngError::InvalidChunkData(format!(
+                "Payload part {} appears more than once",
+                index
+            )));
+        }
+    }
+
+    parts
+        .iter()
+        .enumerate()
+        .map(|(index, part)| {
+            part.ok_or_else(|| {
+                PngError::InvalidChunkData(format!("Payload part {} is missing", index))
+            })
+        })
+        .collect::<Result<Vec<&[u8]>>>()
+        .map(|parts| Cow::Owned(parts.concat()))
+}
+
+/// Reassembles every payload stored in the chunks, in the order they appear. Each chunk that isn't
+/// part of a sequence is a payload of its own and is borrowed; all sequenced parts are joined into
+/// a single payload, placed where its first part appears.
+pub fn join_all<'a>(chunks: impl IntoIterator<Item = &'a Chunk>) -> Result<Vec<Cow<'a, [u8]>>> {
+    let mut payloads = Vec::new();
+    let mut parts = Vec::new();
+    let mut sequence_index = None;
+    for chunk in chunks {
+        match is_sequenced(chunk) {
+            true => {
+                sequence_index.get_or_insert(payloads.len());
+                parts.push(chunk);
+            }
+            false => payloads.push(Cow::Borrowed(chunk.data())),
+        }
+    }
+
+    if let Some(index) = sequence_index {
+        payloads.insert(index, join(parts)?);
+    }
+    Ok(payloads)
+}
+
+/// Removes the payload [`join`] reads from chunks of the given type: the first chunk if it isn't
+/// part of a sequence, otherwise every sequenced part. Returns the removed chunks in file order.
+/// Fails if there is no chunk of the type.
+pub fn remove(png: &mut Png, chunk_type: &str) -> Result<Vec<Chunk>> {
+    let first = png
+        .chunk_by_type(chunk_type)
+        .ok_or_else(|| PngError::ChunkNotFound(chunk_type.to_string()))?;
+    if !is_sequenced(first) {
+        return Ok(vec![png.remove_chunk(chunk_type)?]);
+    }
+
+    Ok(png.remove_chunks(|c| c.chunk_type().bytes() == chunk_type.as_bytes() && is_sequenced(c)))
+}
+
+/// Unwraps a payload reassembled by [`join`]: checks its envelope's signature against `verify`,
+/// decrypts it with `password` and decompresses it, in that order. Data that needs none of these
+/// steps stays borrowed.
+///
+/// Only payloads the envelope marks as encrypted are decrypted; a password given for any other
+/// payload is ignored. Fails with [`PngError::PasswordRequired`] if the payload is encrypted but no
+/// password is given.
+pub fn open<'a>(
+    payload: Cow<'a, [u8]>,
+    password: Option<&str>,
+    verify: Option<&VerifyingKey>,
+) -> Result<Cow<'a, [u8]>> {
+    let (header_len, compression, password) = {
+        let envelope = Envelope::open(&payload)?;
+        if let Some(key) = verify {
+            envelope.verify(key)?;
+        }
+        let password = match (envelope.is_encrypted(), password) {
+            (true, None) => return Err(PngError::PasswordRequired),
+            (true, password) => password,
+            (false, _) => None,
+        };
+        (envelope.header_len(), envelope.compression(), password)
+    };
+    let payload = match payload {
+        Cow::Borrowed(data) => Cow::Borrowed(&data[header_len..]),
+        Cow::Owned(mut data) => {
+            data.drain(..header_len);
+            Cow::Owned(data)
+        }
+    };
+
+    let payload = match password {
+        Some(password) => Cow::Owned(crypto::decrypt(&payload, password)?),
+        None => payload,
+    };
+
+    match compression {
+        Some(compression) => Ok(Cow::Owned(compression.decompress(&payload)?)),
+        None => Ok(payload),
+    }
+}
+
+/// Returns `payload` wrapped in an ASCII armor block, so that it can be sent as text, e.g. after
+/// reading it from a file with [`join`] and [`open`].
+pub fn to_armor(payload: &[u8]) -> String {
+    armor::armor(armor::PAYLOAD_LABEL, payload)
+}
+
+/// Parses a payload from an ASCII armor block produced by [`to_armor`].
+pub fn from_armor(s: &str) -> Result<Vec<u8>> {
+    let (label, bytes) = armor::dearmor(s)?;
+    if label != armor::PAYLOAD_LABEL {
+        return Err(PngError::InvalidArmor(format!(
+            "Expected a {} block, got {}",
+            armor::PAYLOAD_LABEL,
+            label
+        )));
+    }
+
+    Ok(bytes)
+}
+
+fn is_sequenced(chunk: &Chunk) -> bool {
+    chunk.data().len() >= SEQUENCE_HEADER_LEN && chunk.data().starts_with(&SEQUENCE_MAGIC)
+}
+
+fn read_sequence_header(chunk: &Chunk) -> Result<(u32, u32, &[u8])> {
+    if !is_sequenced(chunk) {
+        return Err(PngError::InvalidChunkData(
+            "Chunk is not part of a payload sequence".to_string(),
+        ));
+    }
+
+    let data = chunk.data();
+    let index = u32::from_be_bytes(Chunk::read_4_bytes(data, 4)?);
+    let count = u32::from_be_bytes(Chunk::read_4_bytes(data, 8)?);
+    Ok((index, count, &data[SEQUENCE_HEADER_LEN..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_small_payload_is_stored_as_is() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let chunks = split(&chunk_type, b"secret".to_vec(), 64).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].data(), b"secret");
+        assert_eq!(*join(&chunks).unwrap(), *b"secret");
+    }
+
+    #[test]
+    fn test_join_borrows_single_chunk() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let chunks = split(&chunk_type, b"secret".to_vec(), 64).unwrap();
+        assert!(matches!(join(&chunks).unwrap(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_large_payload_round_trip() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let payload = payload(1000);
+        let chunks = split(&chunk_type, payload.clone(), 64).unwrap();
+
+        // 52 bytes of payload fit in each chunk next to the header.
+        assert_eq!(chunks.len(), 20);
+        assert!(chunks.iter().all(|c| c.length() <= 64));
+        assert!(chunks.iter().all(|c| c.chunk_type().to_string() == "ruSt"));
+        assert_eq!(join(&chunks).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_join_out_of_order() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let payload = payload(200);
+        let chunks = split(&chunk_type, payload.clone(), 64).unwrap();
+        assert_eq!(join(chunks.iter().rev()).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_join_missing_or_duplicate_part() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let chunks = split(&chunk_type, payload(200), 64).unwrap();
+
+        assert!(join(&chunks[1..]).is_err());
+        assert!(join(chunks.iter().chain(&chunks[..1])).is_err());
+    }
+
+    #[test]
+    fn test_payload_starting_with_magic() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let payload = b"pmSQ and then some".to_vec();
+        let chunks = split(&chunk_type, payload.clone(), 64).unwrap();
+        assert_eq!(join(&chunks).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_remove() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let mut png = Png::try_from(crate::png::tests::PNG_FILE.as_ref()).unwrap();
+        png.append_chunk(Chunk::new(chunk_type, b"first".to_vec()));
+        for chunk in split(&chunk_type, payload(200), 64).unwrap() {
+            png.append_chunk(chunk);
+        }
+
+        assert_eq!(remove(&mut png, "ruSt").unwrap().len(), 1);
+        assert_eq!(remove(&mut png, "ruSt").unwrap().len(), 4);
+        assert_eq!(png.chunks().len(), 3);
+        assert!(matches!(
+            remove(&mut png, "ruSt"),
+            Err(PngError::ChunkNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_join_all() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let payload = payload(200);
+        let mut chunks = split(&chunk_type, b"first".to_vec(), 64).unwrap();
+        chunks.extend(split(&chunk_type, payload.clone(), 64).unwrap());
+        chunks.extend(split(&chunk_type, b"last".to_vec(), 64).unwrap());
+
+        let payloads = join_all(&chunks).unwrap();
+        assert_eq!(payloads.len(), 3);
+        assert_eq!(*payloads[0], *b"first");
+        assert_eq!(payloads[1], payload);
+        assert_eq!(*payloads[2], *b"last");
+
+        assert!(join_all(&chunks[..3]).is_err());
+        assert!(join_all(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_max_chunk_size_too_small() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        assert!(split(&chunk_type, payload(200), SEQUENCE_HEADER_LEN).is_err());
+    }
+
+    #[test]
+    fn test_open_sealed_payload() {
+        use crate::compression::Compression;
+        use crate::envelope;
+        use ed25519_dalek::SigningKey;
+
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let message = b"This is where your secret message will be!".repeat(10);
+        let body = Compression::Deflate.compress(&message).unwrap();
+        let body = crypto::encrypt(&body, "hunter2").unwrap();
+        let sealed = envelope::seal(body, Some(Compression::Deflate), true, Some(&key));
+
+        let opened = open(
+            Cow::Borrowed(&sealed),
+            Some("hunter2"),
+            Some(&key.verifying_key()),
+        )
+        .unwrap();
+        assert_eq!(*opened, *message);
+        assert!(open(Cow::Borrowed(&sealed), Some("wrong"), None).is_err());
+        assert!(matches!(
+            open(Cow::Borrowed(&sealed), None, None),
+            Err(PngError::PasswordRequired)
+        ));
+    }
+
+    #[test]
+    fn test_open_plain_payload_is_borrowed() {
+        let opened = open(Cow::Borrowed(b"secret"), None, None).unwrap();
+        assert!(matches!(opened, Cow::Borrowed(b"secret")));
+
+        // A password for a payload that isn't encrypted is ignored.
+        let opened = open(Cow::Borrowed(b"secret"), Some("hunter2"), None).unwrap();
+        assert!(matches!(opened, Cow::Borrowed(b"secret")));
+    }
+
+    #[test]
+    fn test_payload_armor_round_trip() {
+        let payload = payload(300);
+        let armored = to_armor(&payload);
+        assert!(armored.starts_with("-----BEGIN PNGME PAYLOAD-----"));
+        assert_eq!(from_armor(&armored).unwrap(), payload);
+
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), payload);
+        assert!(matches!(
+            from_armor(&chunk.to_armor()),
+            Err(PngError::InvalidArmor(_))
+        ));
+    }
+}
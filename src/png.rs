@@ -1 +1,640 @@
+use std::io::{Read, Write};
 
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::Ihdr;
+use crate::stream::{ChunkReader, ChunkWriter, ParseOptions};
+use crate::validate::{self, Issue, ValidationReport};
+
+use crate::error::PngError;
+use crate::{Error, Result};
+
+/// Where [`Png::insert_chunk`] places a new chunk.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Position {
+    /// Right before `IEND`, or at the end if there is none.
+    BeforeIend,
+    /// Right after `IHDR`, i.e. before any other chunk.
+    AfterIhdr,
+    /// Right after the last `IDAT`.
+    AfterIdat,
+    /// At the given index into the chunk list.
+    Index(usize),
+}
+
+impl std::str::FromStr for Position {
+    type Err = Error;
+
+    /// Parses `before-iend`, `after-ihdr`, `after-idat` or a chunk index.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "before-iend" => Ok(Position::BeforeIend),
+            "after-ihdr" => Ok(Position::AfterIhdr),
+            "after-idat" => Ok(Position::AfterIdat),
+            _ => s.parse().map(Position::Index).map_err(|_| {
+                PngError::InvalidPlacement(format!(
+                    "Unknown position {:?}, expected before-iend, after-ihdr, after-idat or an index",
+                    s
+                ))
+            }),
+        }
+    }
+}
+
+/// A PNG file, i.e. the 8-byte signature followed by a list of chunks.
+#[derive(Serialize)]
+pub struct Png {
+    chunks: Vec<Chunk>,
+}
+
+impl Png {
+    pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+    pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
+        Self { chunks }
+    }
+
+    pub fn signature(&self) -> &[u8; 8] {
+        &Png::STANDARD_HEADER
+    }
+
+    /// Parses the `IHDR` chunk. Fails if there is none or it is malformed.
+    pub fn header(&self) -> Result<Ihdr> {
+        let chunk = self
+            .chunks
+            .iter()
+            .find(|c| *c.chunk_type() == ChunkType::IHDR)
+            .ok_or_else(|| PngError::ChunkNotFound(ChunkType::IHDR.to_string()))?;
+        Ihdr::try_from(chunk)
+    }
+
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    pub fn into_chunks(self) -> Vec<Chunk> {
+        self.chunks
+    }
+
+    /// Appends a chunk to the file. If the last chunk is `IEND`, the new chunk is inserted right
+    /// before it so that `IEND` stays the final chunk.
+    pub fn append_chunk(&mut self, chunk: Chunk) {
+        match self.chunks.last() {
+            Some(last) if *last.chunk_type() == ChunkType::IEND => {
+                let index = self.chunks.len() - 1;
+                self.chunks.insert(index, chunk);
+            }
+            _ => self.chunks.push(chunk),
+        }
+    }
+
+    /// Inserts a chunk at the given position and returns the index it ended up at.
+    ///
+    /// Fails if the position refers to a chunk the file doesn't have, is out of bounds, or if the
+    /// insertion would move `IHDR` away from the start or `IEND` away from the end of the file.
+    pub fn insert_chunk(&mut self, chunk: Chunk, position: Position) -> Result<usize> {
+        let index = match position {
+            Position::BeforeIend => match self.chunks.last() {
+                Some(last) if *last.chunk_type() == ChunkType::IEND => self.chunks.len() - 1,
+                _ => self.chunks.len(),
+            },
+            Position::AfterIhdr => {
+                self.position_of(ChunkType::IHDR)
+                    .ok_or_else(|| PngError::ChunkNotFound("IHDR".to_string()))?
+                    + 1
+            }
+            Position::AfterIdat => {
+                self.chunks
+                    .iter()
+                    .rposition(|c| *c.chunk_type() == ChunkType::IDAT)
+                    .ok_or_else(|| PngError::ChunkNotFound("IDAT".to_string()))?
+                    + 1
+            }
+            Position::Index(index) => index,
+        };
+
+        if index > self.chunks.len() {
+            return Err(PngError::InvalidPlacement(format!(
+                "Index {} is out of bounds for {} chunks",
+                index,
+                self.chunks.len()
+            )));
+        }
+
+        let is_type = |c: Option<&Chunk>, t| c.is_some_and(|c| *c.chunk_type() == t);
+        let chunk_type = *chunk.chunk_type();
+        if (index == 0 && is_type(self.chunks.first(), ChunkType::IHDR))
+            || (chunk_type == ChunkType::IHDR && index != 0)
+        {
+            return Err(PngError::InvalidPlacement(
+                "IHDR must be the first chunk".to_string(),
+            ));
+        }
+        if (index == self.chunks.len() && is_type(self.chunks.last(), ChunkType::IEND))
+            || (chunk_type == ChunkType::IEND && index != self.chunks.len())
+        {
+            return Err(PngError::InvalidPlacement(
+                "IEND must be the last chunk".to_string(),
+            ));
+        }
+
+        self.chunks.insert(index, chunk);
+        Ok(index)
+    }
+
+    /// Inserts chunks at the given position, keeping them together and in order, e.g. the parts of
+    /// a split payload. Fails like [`Png::insert_chunk`].
+    pub fn insert_chunks(&mut self, chunks: Vec<Chunk>, position: Position) -> Result<()> {
+        let mut position = position;
+        for chunk in chunks {
+            let index = self.insert_chunk(chunk, position)?;
+            position = Position::Index(index + 1);
+        }
+        Ok(())
+    }
+
+    /// Removes the first chunk matching the given type code and returns it. Fails if there is no
+    /// such chunk.
+    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
+        let index = self
+            .chunks
+            .iter()
+            .position(|c| c.chunk_type().bytes() == chunk_type.as_bytes())
+            .ok_or_else(|| PngError::ChunkNotFound(chunk_type.to_string()))?;
+
+        Ok(self.chunks.remove(index))
+    }
+
+    /// Removes every chunk `f` returns true for, and returns the removed chunks in file order.
+    pub fn remove_chunks<F: FnMut(&Chunk) -> bool>(&mut self, f: F) -> Vec<Chunk> {
+        let (removed, kept) = std::mem::take(&mut self.chunks).into_iter().partition(f);
+        self.chunks = kept;
+        removed
+    }
+
+    /// Removes all ancillary chunks except those whose type is listed in `keep`, and returns the
+    /// removed chunks in file order. Critical chunks are always kept.
+    pub fn strip_ancillary(&mut self, keep: &[ChunkType]) -> Vec<Chunk> {
+        self.remove_chunks(|c| !c.chunk_type().is_critical() && !keep.contains(c.chunk_type()))
+    }
+
+    fn position_of(&self, chunk_type: ChunkType) -> Option<usize> {
+        self.chunks
+            .iter()
+            .position(|c| *c.chunk_type() == chunk_type)
+    }
+
+    /// Returns the first chunk matching the given type code.
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
+        self.chunks_by_type(chunk_type).next()
+    }
+
+    /// Returns all chunks matching the given type code, in file order.
+    pub fn chunks_by_type<'a>(&'a self, chunk_type: &str) -> impl Iterator<Item = &'a Chunk> {
+        let wanted: Option<[u8; 4]> = chunk_type.as_bytes().try_into().ok();
+        self.chunks
+            .iter()
+            .filter(move |c| Some(c.chunk_type().bytes()) == wanted)
+    }
+
+    /// Checks chunk ordering, duplicate singleton chunks and required chunks. Signature, crcs and
+    /// truncation are already checked while parsing, use [`validate::check`] to report those for
+    /// raw bytes.
+    pub fn validate(&self) -> ValidationReport {
+        let mut offset = Png::STANDARD_HEADER.len();
+        let layout: Vec<(usize, [u8; 4])> = self
+            .chunks
+            .iter()
+            .map(|c| {
+                let entry = (offset, c.chunk_type().bytes());
+                offset += 12 + c.data().len();
+                entry
+            })
+            .collect();
+
+        ValidationReport {
+            issues: validate::check_layout(&layout),
+        }
+    }
+
+    /// Reads a whole PNG file from `reader`, one chunk at a time.
+    pub fn read_from<R: Read>(reader: R) -> Result<Png> {
+        let (png, _) = Png::read_from_with(reader, ParseOptions::default())?;
+        Ok(png)
+    }
+
+    /// Like [`Png::read_from`], but parses according to `options`. Returns the problems the options
+    /// allowed to pass alongside the file.
+    pub fn read_from_with<R: Read>(reader: R, options: ParseOptions) -> Result<(Png, Vec<Issue>)> {
+        let mut reader = ChunkReader::with_options(reader, options);
+        reader.read_signature()?;
+        let chunks = reader.by_ref().collect::<Result<Vec<Chunk>>>()?;
+
+        Ok((Self { chunks }, reader.warnings().to_vec()))
+    }
+
+    /// Like [`Png::try_from`], but parses according to `options`. Returns the problems the options
+    /// allowed to pass alongside the file.
+    pub fn from_bytes_with(bytes: &[u8], options: ParseOptions) -> Result<(Png, Vec<Issue>)> {
+        Png::read_from_with(bytes, options)
+    }
+
+    /// Writes the PNG file to `writer`, one chunk at a time.
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        let mut writer = ChunkWriter::new(writer);
+        writer.write_signature()?;
+        for chunk in &self.chunks {
+            writer.write_chunk(chunk)?;
+        }
+        writer.flush()
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        Png::STANDARD_HEADER
+            .iter()
+            .cloned()
+            .chain(self.chunks.iter().flat_map(|c| c.as_bytes()))
+            .collect()
+    }
+}
+
+impl TryFrom<&[u8]> for Png {
+    type Error = Error;
+
+    /// Checks the 8-byte signature, then reads chunks back to back until the input is exhausted.
+    /// Each chunk spans 12 bytes (length, type code and crc) plus its data length.
+    fn try_from(value: &[u8]) -> Result<Self> {
+        let rest = value
+            .strip_prefix(&Png::STANDARD_HEADER)
+            .ok_or(PngError::InvalidSignature)?;
+
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        while offset < rest.len() {
+            let b_len = Chunk::read_4_bytes(rest, offset)?;
+            let end = (u32::from_be_bytes(b_len) as usize)
+                .checked_add(offset + 12)
+                .ok_or(PngError::UnexpectedEof)?;
+
+            let bytes = rest.get(offset..end).ok_or(PngError::UnexpectedEof)?;
+            chunks.push(Chunk::try_from(bytes)?);
+
+            offset = end;
+        }
+
+        Ok(Self { chunks })
+    }
+}
+
+impl std::fmt::Display for Png {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Png {{")?;
+        writeln!(f, "    Chunks: {}", self.chunks.len())?;
+        for chunk in &self.chunks {
+            write!(f, "    {}: {} bytes", chunk.chunk_type(), chunk.length())?;
+            match chunk.chunk_type().description() {
+                Some(description) => writeln!(f, " ({})", description)?,
+                None => writeln!(f)?,
+            }
+        }
+        writeln!(f, "}}")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    // A 1x1 red RGB image.
+    pub(crate) const PNG_FILE: [u8; 69] = *include_bytes!("../testdata/pixel.png");
+
+    fn chunk_from_strings(chunk_type: &str, data: &str) -> Chunk {
+        let chunk_type = ChunkType::from_str(chunk_type).unwrap();
+        Chunk::new(chunk_type, data.as_bytes().to_vec())
+    }
+
+    fn testing_chunks() -> Vec<Chunk> {
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk"),
+            chunk_from_strings("miDl", "I am another chunk"),
+            chunk_from_strings("LASt", "I am the last chunk"),
+        ]
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(testing_chunks())
+    }
+
+    fn testing_png_bytes() -> Vec<u8> {
+        Png::STANDARD_HEADER
+            .iter()
+            .cloned()
+            .chain(testing_chunks().iter().flat_map(|c| c.as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_chunks() {
+        let png = testing_png();
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(png.signature(), &Png::STANDARD_HEADER);
+    }
+
+    #[test]
+    fn test_valid_from_bytes() {
+        let bytes = testing_png_bytes();
+        let png = Png::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let mut bytes = testing_png_bytes();
+        bytes[0] = 13;
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_chunk() {
+        let mut bytes = testing_png_bytes();
+        // Flip a bit in the data of the first chunk so its crc no longer matches.
+        bytes[20] ^= 1;
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+    }
+
+    #[test]
+    fn test_truncated_chunk() {
+        let bytes = testing_png_bytes();
+        assert!(Png::try_from(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Png::try_from(&bytes[..Png::STANDARD_HEADER.len() + 2]).is_err());
+
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(matches!(
+            Png::try_from(bytes.as_ref()),
+            Err(PngError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn test_list_chunks() {
+        let png = testing_png();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["FrSt", "miDl", "LASt"]);
+    }
+
+    #[test]
+    fn test_chunk_by_type() {
+        let png = testing_png();
+        let chunk = png.chunk_by_type("FrSt").unwrap();
+        assert_eq!(chunk.data_as_string().unwrap(), "I am the first chunk");
+        assert!(png.chunk_by_type("NoNe").is_none());
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am a repeated chunk"));
+
+        let data: Vec<String> = png
+            .chunks_by_type("miDl")
+            .map(|c| c.data_as_string().unwrap())
+            .collect();
+        assert_eq!(data, vec!["I am another chunk", "I am a repeated chunk"]);
+        assert_eq!(png.chunks_by_type("NoNe").count(), 0);
+    }
+
+    #[test]
+    fn test_append_chunk() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "Message"));
+        let chunk = png.chunks().last().unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "TeSt");
+        assert_eq!(chunk.data_as_string().unwrap(), "Message");
+    }
+
+    #[test]
+    fn test_append_chunk_before_iend() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IEND", ""));
+        png.append_chunk(chunk_from_strings("TeSt", "Message"));
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["FrSt", "miDl", "LASt", "TeSt", "IEND"]);
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_insert_chunk() {
+        let cases = [
+            (Position::BeforeIend, 2),
+            (Position::AfterIhdr, 1),
+            (Position::AfterIdat, 2),
+            (Position::Index(2), 2),
+        ];
+
+        for (position, expected) in cases {
+            let mut png = Png::try_from(PNG_FILE.as_ref()).unwrap();
+            let index = png
+                .insert_chunk(chunk_from_strings("ruSt", "Message"), position)
+                .unwrap();
+            assert_eq!(index, expected);
+            assert_eq!(png.chunks()[index].chunk_type().to_string(), "ruSt");
+            assert_eq!(png.chunks().len(), 4);
+        }
+    }
+
+    #[test]
+    fn test_insert_chunk_keeps_ihdr_and_iend_in_place() {
+        let mut png = Png::try_from(PNG_FILE.as_ref()).unwrap();
+        let chunk = || chunk_from_strings("ruSt", "Message");
+
+        assert!(png.insert_chunk(chunk(), Position::Index(0)).is_err());
+        assert!(png.insert_chunk(chunk(), Position::Index(3)).is_err());
+        assert!(png.insert_chunk(chunk(), Position::Index(4)).is_err());
+        assert!(png
+            .insert_chunk(chunk_from_strings("IEND", ""), Position::AfterIhdr)
+            .is_err());
+        assert!(png
+            .insert_chunk(chunk_from_strings("IHDR", ""), Position::Index(1))
+            .is_err());
+        assert_eq!(chunk_types(&png), vec!["IHDR", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_insert_chunk_missing_anchor() {
+        let mut png = testing_png();
+        let chunk = || chunk_from_strings("ruSt", "Message");
+
+        assert!(png.insert_chunk(chunk(), Position::AfterIhdr).is_err());
+        assert!(png.insert_chunk(chunk(), Position::AfterIdat).is_err());
+        assert_eq!(png.insert_chunk(chunk(), Position::BeforeIend).unwrap(), 3);
+        assert_eq!(png.insert_chunk(chunk(), Position::Index(0)).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_insert_chunks_keeps_order() {
+        let mut png = Png::try_from(PNG_FILE.as_ref()).unwrap();
+        let chunks = vec![
+            chunk_from_strings("ruSa", "first"),
+            chunk_from_strings("ruSb", "second"),
+        ];
+        png.insert_chunks(chunks, Position::AfterIhdr).unwrap();
+        assert_eq!(
+            chunk_types(&png),
+            vec!["IHDR", "ruSa", "ruSb", "IDAT", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_display_describes_standard_chunks() {
+        let mut png = Png::try_from(PNG_FILE.as_ref()).unwrap();
+        png.append_chunk(chunk_from_strings("ruSt", "Message"));
+        let display = png.to_string();
+        assert!(display.contains("IHDR: 13 bytes (Image header)\n"));
+        assert!(display.contains("ruSt: 7 bytes\n"));
+    }
+
+    #[test]
+    fn test_position_from_str() {
+        assert_eq!(
+            "before-iend".parse::<Position>().unwrap(),
+            Position::BeforeIend
+        );
+        assert_eq!(
+            "after-idat".parse::<Position>().unwrap(),
+            Position::AfterIdat
+        );
+        assert_eq!("3".parse::<Position>().unwrap(), Position::Index(3));
+        assert!("middle".parse::<Position>().is_err());
+    }
+
+    #[test]
+    fn test_header() {
+        let png = Png::try_from(PNG_FILE.as_ref()).unwrap();
+        let ihdr = png.header().unwrap();
+        assert_eq!((ihdr.width, ihdr.height), (1, 1));
+
+        assert!(matches!(
+            testing_png().header(),
+            Err(PngError::ChunkNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_from_bytes_with() {
+        let mut bytes = PNG_FILE.to_vec();
+        // Last byte of the IDAT crc.
+        bytes[56] ^= 1;
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+
+        let (png, warnings) = Png::from_bytes_with(&bytes, ParseOptions::lenient()).unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE);
+        assert!(matches!(
+            warnings.as_slice(),
+            [Issue::CrcMismatch { offset: 33, chunk_type, .. }] if chunk_type == "IDAT"
+        ));
+
+        let (_, warnings) = Png::from_bytes_with(&PNG_FILE, ParseOptions::lenient()).unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        let png = Png::try_from(PNG_FILE.as_ref()).unwrap();
+        assert!(png.validate().is_valid());
+
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("IHDR", ""));
+        // IHDR isn't first, and IDAT and IEND are missing.
+        assert_eq!(png.validate().issues.len(), 3);
+    }
+
+    #[test]
+    fn test_remove_chunk() {
+        let mut png = testing_png();
+        let removed = png.remove_chunk("miDl").unwrap();
+        assert_eq!(removed.chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunks().len(), 2);
+        assert!(png.chunk_by_type("miDl").is_none());
+        assert!(png.remove_chunk("miDl").is_err());
+    }
+
+    #[test]
+    fn test_strip_ancillary() {
+        let mut png = Png::try_from(PNG_FILE.as_ref()).unwrap();
+        for chunk_type in ["tEXt", "tIME", "ruSt"] {
+            png.append_chunk(chunk_from_strings(chunk_type, "data"));
+        }
+
+        let keep = [ChunkType::TIME];
+        let removed: Vec<String> = png
+            .strip_ancillary(&keep)
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(removed, vec!["tEXt", "ruSt"]);
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "IDAT", "tIME", "IEND"]);
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let bytes = testing_png_bytes();
+        let png = Png::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_png_file_round_trip() {
+        let png = Png::try_from(PNG_FILE.as_ref()).unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "IDAT", "IEND"]);
+        assert_eq!(png.as_bytes(), PNG_FILE);
+    }
+
+    #[test]
+    fn test_png_read_write() {
+        let png = Png::read_from(PNG_FILE.as_ref()).unwrap();
+        assert_eq!(png.chunks().len(), 3);
+
+        let mut bytes = Vec::new();
+        png.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes, PNG_FILE);
+    }
+
+    #[test]
+    fn test_png_trait_impls() {
+        let bytes = testing_png_bytes();
+        let png: Png = TryFrom::try_from(bytes.as_ref()).unwrap();
+        let _png_string = format!("{}", png);
+    }
+}
@@ -0,0 +1,379 @@
+use std::io::{self, Read, Write};
+
+use crate::chunk::{Chunk, CRC};
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::validate::Issue;
+
+use crate::error::PngError;
+use crate::Result;
+
+/// Size of the blocks chunk data is read in. Reading in blocks means a bogus length field can't
+/// make us allocate more memory than the input actually holds.
+const BLOCK_SIZE: usize = 8 * 1024;
+
+/// The spec limits chunk lengths to 2^31 - 1 bytes.
+pub const MAX_CHUNK_LEN: u32 = (1 << 31) - 1;
+
+/// What to do when a chunk's stored crc doesn't match its contents.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CrcMode {
+    /// Fail with [`PngError::CrcMismatch`].
+    Strict,
+    /// Keep the chunk, record an [`Issue::CrcMismatch`] warning and write a correct crc from then
+    /// on.
+    WarnAndRecompute,
+    /// Keep the chunk without a warning.
+    Ignore,
+}
+
+/// Controls how forgiving reading chunks is.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ParseOptions {
+    pub crc: CrcMode,
+    /// Fail with [`PngError::InvalidChunkType`] on type codes that aren't four ASCII letters.
+    /// Otherwise the chunk is kept and an [`Issue::InvalidChunkType`] warning recorded.
+    pub strict_chunk_types: bool,
+    /// Chunks claiming a longer length fail with [`PngError::ChunkTooLong`].
+    pub max_chunk_len: u32,
+}
+
+impl ParseOptions {
+    /// Recomputes bad crcs and keeps chunks with invalid type codes instead of failing,
+    /// collecting a warning for each.
+    pub fn lenient() -> ParseOptions {
+        Self {
+            crc: CrcMode::WarnAndRecompute,
+            strict_chunk_types: false,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            crc: CrcMode::Strict,
+            strict_chunk_types: true,
+            max_chunk_len: MAX_CHUNK_LEN,
+        }
+    }
+}
+
+/// Reads chunks one at a time from an underlying reader, checking each chunk's crc as its bytes
+/// come in.
+pub struct ChunkReader<R: Read> {
+    inner: R,
+    options: ParseOptions,
+    /// Number of bytes read so far, i.e. the file offset of the next chunk.
+    offset: usize,
+    warnings: Vec<Issue>,
+}
+
+impl<R: Read> ChunkReader<R> {
+    pub fn new(inner: R) -> ChunkReader<R> {
+        Self::with_options(inner, ParseOptions::default())
+    }
+
+    pub fn with_options(inner: R, options: ParseOptions) -> ChunkReader<R> {
+        Self {
+            inner,
+            options,
+            offset: 0,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Problems that were tolerated so far because of the parse options.
+    pub fn warnings(&self) -> &[Issue] {
+        &self.warnings
+    }
+
+    /// Reads the 8-byte PNG signature. Fails if it doesn't match [`Png::STANDARD_HEADER`].
+    pub fn read_signature(&mut self) -> Result<()> {
+        let mut header = [0; 8];
+        self.inner.read_exact(&mut header)?;
+        self.offset += header.len();
+
+        match header == Png::STANDARD_HEADER {
+            true => Ok(()),
+            false => Err(PngError::InvalidSignature),
+        }
+    }
+
+    /// Reads the next chunk. Returns `Ok(None)` if the reader is exhausted right at a chunk
+    /// boundary, and an error if it ends in the middle of a chunk.
+    pub fn read_chunk(&mut self) -> Result<Option<Chunk>> {
+        let mut b_len = [0; 4];
+        if !self.read_exact_or_eof(&mut b_len)? {
+            return Ok(None);
+        }
+        let len = u32::from_be_bytes(b_len);
+        if len > self.options.max_chunk_len {
+            return Err(PngError::ChunkTooLong {
+                length: len,
+                max: self.options.max_chunk_len,
+            });
+        }
+        let len = len as usize;
+
+        let mut b_type = [0; 4];
+        self.inner.read_exact(&mut b_type)?;
+        let chunk_type = ChunkType::new_unchecked(b_type);
+        if !chunk_type.bytes_are_alphanumeric() {
+            match self.options.strict_chunk_types {
+                true => return Err(PngError::InvalidChunkType(chunk_type.to_string())),
+                false => self.warnings.push(Issue::InvalidChunkType {
+                    offset: self.offset,
+                    bytes: b_type,
+                }),
+            }
+        }
+
+        let mut digest = CRC.digest();
+        digest.update(&b_type);
+
+        let mut data = Vec::with_capacity(len.min(BLOCK_SIZE));
+        let mut block = [0; BLOCK_SIZE];
+        while data.len() < len {
+            let n = (len - data.len()).min(BLOCK_SIZE);
+            self.inner.read_exact(&mut block[..n])?;
+            digest.update(&block[..n]);
+            data.extend_from_slice(&block[..n]);
+        }
+
+        let mut b_crc = [0; 4];
+        self.inner.read_exact(&mut b_crc)?;
+        let expected = u32::from_be_bytes(b_crc);
+        let actual = digest.finalize();
+        if expected != actual {
+            match self.options.crc {
+                CrcMode::Strict => return Err(PngError::CrcMismatch { expected, actual }),
+                CrcMode::WarnAndRecompute => self.warnings.push(Issue::CrcMismatch {
+                    offset: self.offset,
+                    chunk_type: chunk_type.to_string(),
+                    expected,
+                    actual,
+                }),
+                CrcMode::Ignore => {}
+            }
+        }
+
+        self.offset += 12 + len;
+        Ok(Some(Chunk::new(chunk_type, data)))
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Like `read_exact`, but returns `Ok(false)` instead of an error if the reader is already at
+    /// EOF before the first byte.
+    fn read_exact_or_eof(&mut self, buf: &mut [u8]) -> io::Result<bool> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.inner.read(&mut buf[read..]) {
+                Ok(0) if read == 0 => return Ok(false),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_chunk().transpose()
+    }
+}
+
+/// Writes chunks one at a time to an underlying writer.
+pub struct ChunkWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    pub fn new(inner: W) -> ChunkWriter<W> {
+        Self { inner }
+    }
+
+    pub fn write_signature(&mut self) -> Result<()> {
+        self.inner.write_all(&Png::STANDARD_HEADER)?;
+        Ok(())
+    }
+
+    /// Writes length, type code, data and crc of the chunk without assembling them into a single
+    /// buffer first.
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> Result<()> {
+        let b_type = chunk.chunk_type().bytes();
+
+        let mut digest = CRC.digest();
+        digest.update(&b_type);
+        digest.update(chunk.data());
+
+        self.inner.write_all(&chunk.length().to_be_bytes())?;
+        self.inner.write_all(&b_type)?;
+        self.inner.write_all(chunk.data())?;
+        self.inner.write_all(&digest.finalize().to_be_bytes())?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::PNG_FILE;
+    use std::str::FromStr;
+
+    fn testing_chunk(len: usize) -> Chunk {
+        let chunk_type = ChunkType::from_str("RuSt").unwrap();
+        let data = (0..len).map(|i| i as u8).collect();
+        Chunk::new(chunk_type, data)
+    }
+
+    #[test]
+    fn test_read_png_file() {
+        let mut reader = ChunkReader::new(PNG_FILE.as_ref());
+        reader.read_signature().unwrap();
+
+        let chunks: Vec<Chunk> = reader.collect::<Result<_>>().unwrap();
+        let types: Vec<String> = chunks.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(types, vec!["IHDR", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        // Larger than one block so the data is read in several steps.
+        let chunk = testing_chunk(3 * BLOCK_SIZE + 7);
+
+        let mut writer = ChunkWriter::new(Vec::new());
+        writer.write_signature().unwrap();
+        writer.write_chunk(&chunk).unwrap();
+        let bytes = writer.into_inner();
+
+        let mut reader = ChunkReader::new(bytes.as_slice());
+        reader.read_signature().unwrap();
+        let actual = reader.read_chunk().unwrap().unwrap();
+        assert_eq!(actual.as_bytes(), chunk.as_bytes());
+        assert!(reader.read_chunk().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        let mut reader = ChunkReader::new(&PNG_FILE[1..]);
+        assert!(reader.read_signature().is_err());
+    }
+
+    #[test]
+    fn test_invalid_crc() {
+        let mut bytes = testing_chunk(42).as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+
+        let mut reader = ChunkReader::new(bytes.as_slice());
+        assert!(reader.read_chunk().is_err());
+    }
+
+    #[test]
+    fn test_lenient_crc() {
+        let chunk = testing_chunk(42);
+        let mut bytes = chunk.as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        bytes.extend(testing_chunk(8).as_bytes());
+
+        let options = ParseOptions::lenient();
+        let mut reader = ChunkReader::with_options(bytes.as_slice(), options);
+        let chunks: Vec<Chunk> = reader.by_ref().collect::<Result<_>>().unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].as_bytes(), chunk.as_bytes());
+        assert!(matches!(
+            reader.warnings(),
+            [Issue::CrcMismatch { offset: 0, .. }]
+        ));
+
+        let options = ParseOptions {
+            crc: CrcMode::Ignore,
+            ..Default::default()
+        };
+        let mut reader = ChunkReader::with_options(bytes.as_slice(), options);
+        assert!(reader.read_chunk().unwrap().is_some());
+        assert!(reader.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_chunk_type() {
+        let mut bytes = testing_chunk(8).as_bytes();
+        bytes[4..8].copy_from_slice(b"ru1t");
+        let crc = Chunk::compute_crc(&ChunkType::new_unchecked(*b"ru1t"), &bytes[8..16]);
+        bytes[16..].copy_from_slice(&crc.to_be_bytes());
+
+        let mut reader = ChunkReader::new(bytes.as_slice());
+        assert!(matches!(
+            reader.read_chunk(),
+            Err(PngError::InvalidChunkType(_))
+        ));
+
+        let mut reader = ChunkReader::with_options(bytes.as_slice(), ParseOptions::lenient());
+        let chunk = reader.read_chunk().unwrap().unwrap();
+        assert_eq!(chunk.chunk_type().bytes(), *b"ru1t");
+        assert_eq!(
+            reader.warnings(),
+            [Issue::InvalidChunkType {
+                offset: 0,
+                bytes: *b"ru1t"
+            }]
+        );
+    }
+
+    #[test]
+    fn test_max_chunk_len() {
+        let bytes = testing_chunk(42).as_bytes();
+        let options = ParseOptions {
+            max_chunk_len: 41,
+            ..Default::default()
+        };
+        let mut reader = ChunkReader::with_options(bytes.as_slice(), options);
+        assert!(matches!(
+            reader.read_chunk(),
+            Err(PngError::ChunkTooLong {
+                length: 42,
+                max: 41
+            })
+        ));
+    }
+
+    #[test]
+    fn test_truncated_chunk() {
+        let bytes = testing_chunk(42).as_bytes();
+
+        let mut reader = ChunkReader::new(&bytes[..bytes.len() - 1]);
+        assert!(reader.read_chunk().is_err());
+
+        let mut reader = ChunkReader::new(&bytes[..2]);
+        assert!(reader.read_chunk().is_err());
+    }
+
+    #[test]
+    fn test_bogus_length() {
+        // Claims 4 GiB of data but ends right after the type code.
+        let bytes = [255, 255, 255, 255, 82, 117, 83, 116];
+        let mut reader = ChunkReader::new(bytes.as_ref());
+        assert!(reader.read_chunk().is_err());
+    }
+}
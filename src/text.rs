@@ -0,0 +1,348 @@
+use serde::Serialize;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::compression::Compression;
+
+use crate::error::PngError;
+use crate::{Error, Result};
+
+/// Keywords must be 1-79 bytes long.
+const MAX_KEYWORD_LEN: usize = 79;
+
+/// The only compression method defined by the spec, zlib deflate.
+const COMPRESSION_METHOD_DEFLATE: u8 = 0;
+
+/// Which of the three standard textual chunks a [`TextChunk`] is stored as.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TextKind {
+    /// `tEXt`: uncompressed Latin-1 text.
+    Text,
+    /// `zTXt`: zlib-compressed Latin-1 text.
+    Compressed,
+    /// `iTXt`: UTF-8 text with a language tag, optionally zlib-compressed.
+    International { compressed: bool },
+}
+
+impl TextKind {
+    pub fn chunk_type(&self) -> ChunkType {
+        match self {
+            TextKind::Text => ChunkType::TEXT,
+            TextKind::Compressed => ChunkType::ZTXT,
+            TextKind::International { .. } => ChunkType::ITXT,
+        }
+    }
+}
+
+/// A keyword and text pair stored in a `tEXt`, `zTXt` or `iTXt` chunk.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct TextChunk {
+    kind: TextKind,
+    keyword: String,
+    text: String,
+    language_tag: String,
+    translated_keyword: String,
+}
+
+impl TextChunk {
+    pub fn new(kind: TextKind, keyword: &str, text: &str) -> TextChunk {
+        Self {
+            kind,
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+            language_tag: String::new(),
+            translated_keyword: String::new(),
+        }
+    }
+
+    /// Creates an `iTXt` chunk carrying a language tag (e.g. `en-GB`) and the keyword translated
+    /// into that language.
+    pub fn international(
+        keyword: &str,
+        text: &str,
+        language_tag: &str,
+        translated_keyword: &str,
+        compressed: bool,
+    ) -> TextChunk {
+        Self {
+            kind: TextKind::International { compressed },
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+            language_tag: language_tag.to_string(),
+            translated_keyword: translated_keyword.to_string(),
+        }
+    }
+
+    pub fn kind(&self) -> TextKind {
+        self.kind
+    }
+
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The language tag of an `iTXt` chunk, empty for other kinds.
+    pub fn language_tag(&self) -> &str {
+        &self.language_tag
+    }
+
+    /// The translated keyword of an `iTXt` chunk, empty for other kinds.
+    pub fn translated_keyword(&self) -> &str {
+        &self.translated_keyword
+    }
+
+    /// Returns whether the chunk type is one of `tEXt`, `zTXt` or `iTXt`.
+    pub fn is_text_chunk(chunk_type: &ChunkType) -> bool {
+        [ChunkType::TEXT, ChunkType::ZTXT, ChunkType::ITXT].contains(chunk_type)
+    }
+
+    /// Encodes the keyword and text into a chunk. Fails if the keyword is empty, too long or,
+    /// for `tEXt` and `zTXt`, if either can't be represented in Latin-1.
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = encode_latin1(&self.keyword)?;
+        if data.is_empty() || data.len() > MAX_KEYWORD_LEN || data.contains(&0) {
+            return Err(PngError::InvalidChunkData(format!(
+                "Keyword must be 1-{} characters long and not contain NUL",
+                MAX_KEYWORD_LEN
+            )));
+        }
+        data.push(0);
+
+        match self.kind {
+            TextKind::Text => data.extend(encode_latin1(&self.text)?),
+            TextKind::Compressed => {
+                data.push(COMPRESSION_METHOD_DEFLATE);
+                data.extend(Compression::Deflate.compress(&encode_latin1(&self.text)?)?);
+            }
+            TextKind::International { compressed } => {
+                data.push(compressed as u8);
+                data.push(COMPRESSION_METHOD_DEFLATE);
+                data.extend(self.language_tag.as_bytes());
+                data.push(0);
+                data.extend(self.translated_keyword.as_bytes());
+                data.push(0);
+                match compressed {
+                    true => data.extend(Compression::Deflate.compress(self.text.as_bytes())?),
+                    false => data.extend(self.text.as_bytes()),
+                }
+            }
+        }
+
+        Ok(Chunk::new(self.kind.chunk_type(), data))
+    }
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = Error;
+
+    /// Decodes `tEXt` and `zTXt` chunks as Latin-1 and `iTXt` chunks as UTF-8, decompressing the
+    /// text where needed.
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        let (keyword, rest) = split_at_nul(chunk.data())
+            .ok_or_else(|| invalid("Missing NUL separator after keyword"))?;
+        let keyword = Chunk::decode_latin1(keyword);
+
+        match *chunk.chunk_type() {
+            ChunkType::TEXT => Ok(Self::new(
+                TextKind::Text,
+                &keyword,
+                &Chunk::decode_latin1(rest),
+            )),
+            ChunkType::ZTXT => {
+                let (&method, compressed) = rest
+                    .split_first()
+                    .ok_or_else(|| invalid("Missing compression method"))?;
+                check_compression_method(method)?;
+                let text = Compression::Deflate.decompress(compressed)?;
+                let text = Chunk::decode_latin1(&text);
+                Ok(Self::new(TextKind::Compressed, &keyword, &text))
+            }
+            ChunkType::ITXT => {
+                let [flag, method, rest @ ..] = rest else {
+                    return Err(invalid("Missing compression flag or method"));
+                };
+                let (language_tag, rest) =
+                    split_at_nul(rest).ok_or_else(|| invalid("Missing language tag"))?;
+                let (translated_keyword, text) =
+                    split_at_nul(rest).ok_or_else(|| invalid("Missing translated keyword"))?;
+
+                let compressed = match flag {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(invalid("Compression flag must be 0 or 1")),
+                };
+                let text = match compressed {
+                    true => {
+                        check_compression_method(*method)?;
+                        String::from_utf8(Compression::Deflate.decompress(text)?)?
+                    }
+                    false => std::str::from_utf8(text)?.to_string(),
+                };
+
+                Ok(Self::international(
+                    &keyword,
+                    &text,
+                    std::str::from_utf8(language_tag)?,
+                    std::str::from_utf8(translated_keyword)?,
+                    compressed,
+                ))
+            }
+            _ => Err(invalid(&format!(
+                "Expected a text chunk, got {}",
+                chunk.chunk_type()
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for TextChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.kind.chunk_type(), self.keyword)?;
+        if !self.language_tag.is_empty() {
+            write!(f, " [{}]", self.language_tag)?;
+        }
+        write!(f, ": {}", self.text)
+    }
+}
+
+fn invalid(reason: &str) -> PngError {
+    PngError::InvalidChunkData(reason.to_string())
+}
+
+fn split_at_nul(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let i = bytes.iter().position(|&b| b == 0)?;
+    Some((&bytes[..i], &bytes[i + 1..]))
+}
+
+fn encode_latin1(s: &str) -> Result<Vec<u8>> {
+    s.chars()
+        .map(|c| u8::try_from(c).map_err(|_| invalid(&format!("{:?} is not Latin-1", c))))
+        .collect()
+}
+
+fn check_compression_method(method: u8) -> Result<()> {
+    match method {
+        COMPRESSION_METHOD_DEFLATE => Ok(()),
+        _ => Err(invalid(&format!("Unknown compression method {}", method))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::MAX_DECOMPRESSED_LEN;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_text_round_trip() {
+        let text = TextChunk::new(TextKind::Text, "Title", "Café");
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(*chunk.chunk_type(), ChunkType::TEXT);
+        assert_eq!(chunk.data(), b"Title\0Caf\xe9");
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let message = "This is where your secret message will be! ".repeat(20);
+        let text = TextChunk::new(TextKind::Compressed, "Comment", &message);
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(*chunk.chunk_type(), ChunkType::ZTXT);
+        assert!(chunk.data().len() < message.len());
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_international_round_trip() {
+        for compressed in [false, true] {
+            let text = TextChunk::international("Title", "日本語", "ja", "タイトル", compressed);
+            let chunk = text.to_chunk().unwrap();
+            assert_eq!(*chunk.chunk_type(), ChunkType::ITXT);
+
+            let actual = TextChunk::try_from(&chunk).unwrap();
+            assert_eq!(actual, text);
+            assert_eq!(actual.language_tag(), "ja");
+            assert_eq!(actual.translated_keyword(), "タイトル");
+        }
+    }
+
+    #[test]
+    fn test_invalid_keyword() {
+        assert!(TextChunk::new(TextKind::Text, "", "text")
+            .to_chunk()
+            .is_err());
+        assert!(TextChunk::new(TextKind::Text, &"k".repeat(80), "text")
+            .to_chunk()
+            .is_err());
+        assert!(TextChunk::new(TextKind::Text, "日本", "text")
+            .to_chunk()
+            .is_err());
+    }
+
+    #[test]
+    fn test_non_latin1_text() {
+        assert!(TextChunk::new(TextKind::Text, "Title", "日本語")
+            .to_chunk()
+            .is_err());
+        assert!(TextChunk::new(
+            TextKind::International { compressed: false },
+            "Title",
+            "日本語"
+        )
+        .to_chunk()
+        .is_ok());
+    }
+
+    #[test]
+    fn test_invalid_text_chunks() {
+        let chunk = Chunk::new(ChunkType::TEXT, b"no nul".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+
+        let chunk = Chunk::new(ChunkType::ZTXT, b"k\0\0junk".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+
+        let chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), b"k\0v".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_decompressed_text_is_capped() {
+        let mut data = b"Bomb\0\0".to_vec();
+        data.extend(
+            Compression::Deflate
+                .compress(&vec![b'a'; MAX_DECOMPRESSED_LEN as usize + 1])
+                .unwrap(),
+        );
+        let chunk = Chunk::new(ChunkType::ZTXT, data);
+        assert!(TextChunk::try_from(&chunk).is_err());
+
+        let mut data = b"Fine\0\0".to_vec();
+        data.extend(
+            Compression::Deflate
+                .compress(&vec![b'a'; MAX_DECOMPRESSED_LEN as usize])
+                .unwrap(),
+        );
+        let chunk = Chunk::new(ChunkType::ZTXT, data);
+        assert!(TextChunk::try_from(&chunk).is_ok());
+    }
+
+    #[test]
+    fn test_is_text_chunk() {
+        assert!(TextChunk::is_text_chunk(&ChunkType::TEXT));
+        assert!(TextChunk::is_text_chunk(&ChunkType::ITXT));
+        assert!(!TextChunk::is_text_chunk(
+            &ChunkType::from_str("RuSt").unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_text_display() {
+        let text = TextChunk::international("Title", "Hallo", "de", "Titel", false);
+        assert_eq!(text.to_string(), "iTXt Title [de]: Hallo");
+    }
+}
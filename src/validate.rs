@@ -0,0 +1,484 @@
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::chunk::{Chunk, CRC};
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+use crate::Result;
+
+/// Chunks that may appear at most once per file.
+const SINGLETONS: [&[u8; 4]; 18] = [
+    b"IHDR", b"PLTE", b"IEND", b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"cICP", b"mDCv",
+    b"cLLi", b"bKGD", b"hIST", b"tRNS", b"pHYs", b"tIME", b"eXIf", b"acTL",
+];
+
+/// Chunks that must appear before `PLTE` and the image data.
+const BEFORE_PLTE: [&[u8; 4]; 8] = [
+    b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"cICP", b"mDCv", b"cLLi",
+];
+
+/// Chunks that must appear after `PLTE` (if there is one) but before the image data.
+const AFTER_PLTE: [&[u8; 4]; 3] = [b"bKGD", b"hIST", b"tRNS"];
+
+/// Chunks that must appear before the image data but are indifferent to `PLTE`.
+const BEFORE_IDAT: [&[u8; 4]; 4] = [b"pHYs", b"sPLT", b"eXIf", b"acTL"];
+
+/// A single spec violation found in a file. Offsets are byte offsets into the file, pointing at
+/// the start of the chunk (its length field) or of the offending data.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Issue {
+    InvalidSignature,
+    /// A chunk claims more bytes than the file holds.
+    Truncated {
+        offset: usize,
+    },
+    InvalidChunkType {
+        offset: usize,
+        bytes: [u8; 4],
+    },
+    CrcMismatch {
+        offset: usize,
+        chunk_type: String,
+        expected: u32,
+        actual: u32,
+    },
+    MissingChunk {
+        chunk_type: String,
+    },
+    Misordered {
+        offset: usize,
+        chunk_type: String,
+        rule: &'static str,
+    },
+    Duplicate {
+        offset: usize,
+        chunk_type: String,
+    },
+    TrailingData {
+        offset: usize,
+        len: usize,
+    },
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::InvalidSignature => write!(f, "Invalid PNG signature"),
+            Issue::Truncated { offset } => write!(f, "Chunk at offset {} is truncated", offset),
+            Issue::InvalidChunkType { offset, bytes } => write!(
+                f,
+                "Chunk at offset {} has an invalid type code \"{}\"",
+                offset,
+                ChunkType::new_unchecked(*bytes)
+            ),
+            Issue::CrcMismatch {
+                offset,
+                chunk_type,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} chunk at offset {} has crc {} but its data has a crc of {}",
+                chunk_type, offset, expected, actual
+            ),
+            Issue::MissingChunk { chunk_type } => write!(f, "Missing {} chunk", chunk_type),
+            Issue::Misordered {
+                offset,
+                chunk_type,
+                rule,
+            } => write!(f, "{} chunk at offset {} {}", chunk_type, offset, rule),
+            Issue::Duplicate { offset, chunk_type } => {
+                write!(f, "Duplicate {} chunk at offset {}", chunk_type, offset)
+            }
+            Issue::TrailingData { offset, len } => write!(
+                f,
+                "{} bytes of trailing data after IEND at offset {}",
+                len, offset
+            ),
+        }
+    }
+}
+
+/// All issues found in a file, in the order they were encountered.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct ValidationReport {
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl std::fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_valid() {
+            return writeln!(f, "No issues found");
+        }
+
+        for issue in &self.issues {
+            writeln!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// A chunk as found in the file, before its type code or crc have been checked.
+struct RawChunk<'a> {
+    offset: usize,
+    chunk_type: [u8; 4],
+    data: &'a [u8],
+    crc_valid: bool,
+}
+
+impl RawChunk<'_> {
+    fn type_valid(&self) -> bool {
+        self.chunk_type.iter().all(|&b| ChunkType::is_valid_byte(b))
+    }
+}
+
+/// Scans the raw bytes of a file and reports all spec violations instead of stopping at the
+/// first one like [`Png::try_from`] does.
+pub fn check(bytes: &[u8]) -> ValidationReport {
+    let (chunks, mut issues) = scan(bytes);
+
+    let layout: Vec<(usize, [u8; 4])> = chunks
+        .iter()
+        .filter(|c| c.type_valid())
+        .map(|c| (c.offset, c.chunk_type))
+        .collect();
+    issues.extend(check_layout(&layout));
+
+    ValidationReport { issues }
+}
+
+/// Rebuilds a file from whatever can be salvaged from `bytes`.
+///
+/// Critical chunks with a crc mismatch are kept and get their crc recomputed. Ancillary chunks with
+/// a crc mismatch, chunks with an invalid type code, duplicates of singleton chunks and anything
+/// after `IEND` are dropped. A missing `IEND` is added.
+pub fn repair(bytes: &[u8]) -> Result<Png> {
+    let (raw_chunks, _) = scan(bytes);
+
+    let mut seen = HashSet::new();
+    let mut chunks = Vec::new();
+    for raw in raw_chunks {
+        if !raw.type_valid() {
+            continue;
+        }
+
+        let chunk_type = ChunkType::try_from(raw.chunk_type)?;
+        if !raw.crc_valid && !chunk_type.is_critical() {
+            continue;
+        }
+        if SINGLETONS.contains(&&raw.chunk_type) && !seen.insert(raw.chunk_type) {
+            continue;
+        }
+        if &raw.chunk_type == b"IEND" {
+            continue;
+        }
+
+        // The crc isn't stored, so writing the chunk back out recomputes it.
+        chunks.push(Chunk::new(chunk_type, raw.data.to_vec()));
+    }
+    chunks.push(Chunk::new(ChunkType::IEND, Vec::new()));
+
+    Ok(Png::from_chunks(chunks))
+}
+
+/// Splits the file into raw chunks, collecting issues about the signature, truncation, type codes,
+/// crcs and data after `IEND` along the way.
+fn scan(bytes: &[u8]) -> (Vec<RawChunk<'_>>, Vec<Issue>) {
+    let mut issues = Vec::new();
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        issues.push(Issue::InvalidSignature);
+    }
+
+    let mut chunks = Vec::new();
+    let mut offset = Png::STANDARD_HEADER.len().min(bytes.len());
+    while offset < bytes.len() {
+        let rest = &bytes[offset..];
+        let Ok(b_len) = Chunk::read_4_bytes(rest, 0) else {
+            issues.push(Issue::Truncated { offset });
+            break;
+        };
+        let end = (u32::from_be_bytes(b_len) as usize).checked_add(12);
+        let Some(end) = end.filter(|&end| end <= rest.len()) else {
+            issues.push(Issue::Truncated { offset });
+            break;
+        };
+
+        let chunk_type: [u8; 4] = rest[4..8].try_into().expect("Slice has length 4");
+        let data = &rest[8..end - 4];
+        let expected =
+            u32::from_be_bytes(rest[end - 4..end].try_into().expect("Slice has length 4"));
+        let actual = crc_of(chunk_type, data);
+
+        let raw = RawChunk {
+            offset,
+            chunk_type,
+            data,
+            crc_valid: expected == actual,
+        };
+        if !raw.type_valid() {
+            issues.push(Issue::InvalidChunkType {
+                offset,
+                bytes: chunk_type,
+            });
+        } else if !raw.crc_valid {
+            issues.push(Issue::CrcMismatch {
+                offset,
+                chunk_type: ChunkType::new_unchecked(chunk_type).to_string(),
+                expected,
+                actual,
+            });
+        }
+        chunks.push(raw);
+        offset += end;
+
+        if &chunk_type == b"IEND" && offset < bytes.len() {
+            issues.push(Issue::TrailingData {
+                offset,
+                len: bytes.len() - offset,
+            });
+            break;
+        }
+    }
+
+    (chunks, issues)
+}
+
+/// Computes the crc without going through [`ChunkType`], since the type code may be invalid.
+fn crc_of(chunk_type: [u8; 4], data: &[u8]) -> u32 {
+    let mut digest = CRC.digest();
+    digest.update(&chunk_type);
+    digest.update(data);
+    digest.finalize()
+}
+
+/// Checks chunk ordering, duplicates and required chunks, given each chunk's offset and type code.
+pub(crate) fn check_layout(chunks: &[(usize, [u8; 4])]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let name = |t: &[u8; 4]| ChunkType::new_unchecked(*t).to_string();
+    let first_of = |t: &[u8; 4]| chunks.iter().position(|(_, c)| c == t);
+
+    for required in [b"IHDR", b"IDAT", b"IEND"] {
+        if first_of(required).is_none() {
+            issues.push(Issue::MissingChunk {
+                chunk_type: name(required),
+            });
+        }
+    }
+
+    let plte = first_of(b"PLTE");
+    let first_idat = first_of(b"IDAT");
+    let mut seen = HashSet::new();
+
+    for (index, (offset, chunk_type)) in chunks.iter().enumerate() {
+        let misordered = |rule| Issue::Misordered {
+            offset: *offset,
+            chunk_type: name(chunk_type),
+            rule,
+        };
+        let before = |other: Option<usize>| other.is_some_and(|other| index > other);
+
+        if SINGLETONS.contains(&chunk_type) && !seen.insert(chunk_type) {
+            issues.push(Issue::Duplicate {
+                offset: *offset,
+                chunk_type: name(chunk_type),
+            });
+        }
+
+        match chunk_type {
+            b"IHDR" if index != 0 => issues.push(misordered("must be the first chunk")),
+            b"IEND" if index != chunks.len() - 1 => {
+                issues.push(misordered("must be the last chunk"))
+            }
+            b"PLTE" if before(first_idat) => issues.push(misordered("must come before IDAT")),
+            b"IDAT" => {
+                let consecutive = index == 0 || chunks[index - 1].1 == *b"IDAT";
+                if Some(index) != first_idat && !consecutive {
+                    issues.push(misordered("must directly follow the previous IDAT"));
+                }
+            }
+            t if BEFORE_PLTE.contains(&t) && (before(plte) || before(first_idat)) => {
+                issues.push(misordered("must come before PLTE and IDAT"))
+            }
+            t if AFTER_PLTE.contains(&t) && plte.is_some_and(|plte| index < plte) => {
+                issues.push(misordered("must come after PLTE"))
+            }
+            t if (AFTER_PLTE.contains(&t) || BEFORE_IDAT.contains(&t)) && before(first_idat) => {
+                issues.push(misordered("must come before IDAT"))
+            }
+            _ => {}
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::PNG_FILE;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn png_bytes(chunks: &[Chunk]) -> Vec<u8> {
+        Png::STANDARD_HEADER
+            .iter()
+            .copied()
+            .chain(chunks.iter().flat_map(|c| c.as_bytes()))
+            .collect()
+    }
+
+    fn ihdr() -> Chunk {
+        chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0])
+    }
+
+    #[test]
+    fn test_valid_file() {
+        let report = check(&PNG_FILE);
+        assert!(report.is_valid(), "{}", report);
+        assert_eq!(report.to_string(), "No issues found\n");
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[1] = 0;
+        assert_eq!(check(&bytes).issues, vec![Issue::InvalidSignature]);
+    }
+
+    #[test]
+    fn test_crc_mismatch() {
+        let mut bytes = PNG_FILE.to_vec();
+        // Last byte of the IHDR crc.
+        bytes[32] ^= 1;
+        let report = check(&bytes);
+        assert!(matches!(
+            report.issues.as_slice(),
+            [Issue::CrcMismatch { offset: 8, .. }]
+        ));
+    }
+
+    #[test]
+    fn test_truncated_and_trailing_data() {
+        let report = check(&PNG_FILE[..PNG_FILE.len() - 2]);
+        assert!(report.issues.contains(&Issue::Truncated { offset: 57 }));
+        assert!(report.issues.contains(&Issue::MissingChunk {
+            chunk_type: "IEND".to_string()
+        }));
+
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"garbage");
+        assert_eq!(
+            check(&bytes).issues,
+            vec![Issue::TrailingData { offset: 69, len: 7 }]
+        );
+
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(check(&bytes)
+            .issues
+            .contains(&Issue::Truncated { offset: 8 }));
+    }
+
+    #[test]
+    fn test_invalid_chunk_type_display() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[37..41].copy_from_slice(&[0x8B, b'u', b'1', b't']);
+        let issue = check(&bytes).issues.remove(0);
+        assert_eq!(
+            issue.to_string(),
+            "Chunk at offset 33 has an invalid type code \"\\x8Bu1t\""
+        );
+    }
+
+    #[test]
+    fn test_report_serialize() {
+        let report = ValidationReport {
+            issues: vec![Issue::TrailingData { offset: 69, len: 7 }],
+        };
+        assert_eq!(
+            serde_json::to_value(report).unwrap(),
+            serde_json::json!({
+                "issues": [{ "kind": "trailing_data", "offset": 69, "len": 7 }],
+            })
+        );
+    }
+
+    #[test]
+    fn test_ordering_and_duplicates() {
+        let bytes = png_bytes(&[
+            ihdr(),
+            chunk("IDAT", b"a"),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("IDAT", b"b"),
+            chunk("PLTE", &[0, 0, 0]),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("IEND", b""),
+        ]);
+        let report = check(&bytes);
+        let rules: Vec<String> = report.issues.iter().map(|i| i.to_string()).collect();
+
+        assert!(rules
+            .iter()
+            .any(|r| r.starts_with("gAMA") && r.contains("before PLTE")));
+        assert!(rules
+            .iter()
+            .any(|r| r.starts_with("IDAT") && r.contains("follow")));
+        assert!(rules
+            .iter()
+            .any(|r| r.starts_with("PLTE") && r.contains("before IDAT")));
+        assert!(rules.iter().any(|r| r.starts_with("Duplicate gAMA")));
+    }
+
+    #[test]
+    fn test_missing_chunks() {
+        let bytes = png_bytes(&[chunk("ruSt", b"secret")]);
+        let report = check(&bytes);
+        assert_eq!(report.issues.len(), 3);
+        assert!(report
+            .issues
+            .iter()
+            .all(|i| matches!(i, Issue::MissingChunk { .. })));
+    }
+
+    #[test]
+    fn test_repair() {
+        let mut bytes = png_bytes(&[
+            ihdr(),
+            chunk("tEXt", b"Title\0Broken"),
+            chunk("IDAT", b"a"),
+            chunk("IEND", b""),
+        ]);
+        // Corrupt the crc of IHDR (critical) and tEXt (ancillary).
+        bytes[32] ^= 1;
+        bytes[56] ^= 1;
+        bytes.extend_from_slice(b"garbage");
+        assert_eq!(check(&bytes).issues.len(), 3);
+
+        let png = repair(&bytes).unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "IDAT", "IEND"]);
+        assert!(check(&png.as_bytes()).is_valid());
+    }
+
+    #[test]
+    fn test_repair_adds_iend() {
+        let png = repair(&PNG_FILE[..PNG_FILE.len() - 12]).unwrap();
+        assert_eq!(png.as_bytes(), PNG_FILE);
+    }
+}
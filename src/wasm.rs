@@ -0,0 +1,56 @@
+//! `wasm-bindgen` bindings for hiding messages in PNG files held in memory, e.g. in a browser.
+//!
+//! Messages are stored the same way as by `pngme encode`, so files can be decoded by either side.
+//! Errors are thrown as JavaScript `Error`s.
+
+use std::str::FromStr;
+
+use wasm_bindgen::prelude::*;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::envelope;
+use crate::payload::{self, DEFAULT_MAX_CHUNK_SIZE};
+use crate::png::{Png, Position};
+
+/// Stores `message` in chunks of the given type before `IEND` and returns the new file.
+#[wasm_bindgen]
+pub fn encode(png_bytes: &[u8], chunk_type: &str, message: &str) -> Result<Vec<u8>, JsError> {
+    let mut png = Png::try_from(png_bytes)?;
+    let chunk_type = ChunkType::from_str(chunk_type)?;
+
+    let payload = envelope::seal(message.as_bytes().to_vec(), None, false, None);
+    let chunks = payload::split(&chunk_type, payload, DEFAULT_MAX_CHUNK_SIZE)?;
+    png.insert_chunks(chunks, Position::BeforeIend)?;
+    Ok(png.as_bytes())
+}
+
+/// Returns the message stored in chunks of the given type, or `undefined` if there are none.
+/// Signatures aren't checked, and encrypted messages fail to decode.
+#[wasm_bindgen]
+pub fn decode(png_bytes: &[u8], chunk_type: &str) -> Result<Option<String>, JsError> {
+    let png = Png::try_from(png_bytes)?;
+    let chunks: Vec<&Chunk> = png.chunks_by_type(chunk_type).collect();
+    if chunks.is_empty() {
+        return Ok(None);
+    }
+
+    let payload = payload::open(payload::join(chunks)?, None, None)?;
+    Ok(Some(String::from_utf8(payload.into_owned())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::PNG_FILE;
+
+    #[test]
+    fn test_encode_and_decode() {
+        let encoded = encode(&PNG_FILE, "ruSt", "This is a secret message!").unwrap();
+        assert_eq!(
+            decode(&encoded, "ruSt").unwrap().as_deref(),
+            Some("This is a secret message!")
+        );
+        assert_eq!(decode(&encoded, "teSt").unwrap(), None);
+    }
+}